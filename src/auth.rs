@@ -0,0 +1,160 @@
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use axum::Router;
+use axum::extract::{Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use tower_service::Service;
+
+/// Load the HTTP bearer token: `--token` / `MCP_SCREENSHOT_TOKEN` first,
+/// then the file named by `--token-file` / `MCP_SCREENSHOT_TOKEN_FILE`.
+/// Returns `None` when no token is configured (authentication disabled).
+pub fn load_token(
+    token: Option<String>,
+    token_file: Option<PathBuf>,
+) -> Result<Option<Arc<str>>, Box<dyn std::error::Error>> {
+    let token = match (token, token_file) {
+        (Some(token), _) => token,
+        (None, Some(path)) => std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read token file {}: {e}", path.display()))?,
+        (None, None) => return Ok(None),
+    };
+
+    let token = token.trim();
+    if token.is_empty() {
+        return Err("HTTP bearer token is empty".into());
+    }
+    Ok(Some(Arc::from(token)))
+}
+
+/// Mount the MCP `service` at `/mcp`, behind [`require_bearer`] when a token is configured.
+pub fn mcp_router<S>(service: S, token: Option<Arc<str>>) -> Router
+where
+    S: Service<Request, Error = Infallible> + Clone + Send + Sync + 'static,
+    S::Response: IntoResponse,
+    S::Future: Send + 'static,
+{
+    let router = Router::new().nest_service("/mcp", service);
+    match token {
+        Some(token) => {
+            tracing::info!("HTTP bearer token authentication enabled");
+            router.layer(axum::middleware::from_fn_with_state(token, require_bearer))
+        }
+        None => {
+            tracing::warn!("HTTP transport has no authentication; set MCP_SCREENSHOT_TOKEN to require a bearer token");
+            router
+        }
+    }
+}
+
+/// axum middleware rejecting requests without `Authorization: Bearer <token>` with 401.
+pub async fn require_bearer(
+    State(token): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|presented| constant_time_eq(presented.as_bytes(), token.as_bytes()));
+
+    if authorized {
+        next.run(request).await
+    } else {
+        tracing::warn!("Rejected unauthenticated HTTP request to {}", request.uri());
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            "Unauthorized",
+        )
+            .into_response()
+    }
+}
+
+/// Compare without short-circuiting on the first differing byte, so response
+/// timing does not reveal how much of the token was guessed correctly.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    /// Send a request through the same router `serve_http` builds, with a stub MCP service.
+    async fn status(token: Option<&str>, uri: &str, authorization: Option<&str>) -> StatusCode {
+        let service = tower::service_fn(|_: Request| async { Ok::<_, Infallible>("ok") });
+        let router = mcp_router(service, token.map(Arc::from));
+        let mut request = Request::builder().uri(uri);
+        if let Some(value) = authorization {
+            request = request.header(header::AUTHORIZATION, value);
+        }
+        let response = router
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        response.status()
+    }
+
+    #[tokio::test]
+    async fn mcp_router_requires_the_bearer_token() {
+        let token = Some("s3cret");
+        assert_eq!(status(token, "/mcp", None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            status(token, "/mcp", Some("Bearer wrong")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(token, "/mcp", Some("s3cret")).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(token, "/mcp", Some("Bearer s3cret")).await,
+            StatusCode::OK
+        );
+        // Unknown paths are rejected before routing reveals anything
+        assert_eq!(
+            status(token, "/other", None).await,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[tokio::test]
+    async fn mcp_router_without_a_token_is_open() {
+        assert_eq!(status(None, "/mcp", None).await, StatusCode::OK);
+        assert_eq!(status(None, "/other", None).await, StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn load_token_prefers_the_flag_and_trims_the_file() {
+        let path =
+            std::env::temp_dir().join(format!("mcp-screenshot-token-{}", std::process::id()));
+        std::fs::write(&path, "  from-file\n").unwrap();
+
+        let from_file = load_token(None, Some(path.clone())).unwrap();
+        let from_flag = load_token(Some("from-flag".into()), Some(path.clone())).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(from_file.as_deref(), Some("from-file"));
+        assert_eq!(from_flag.as_deref(), Some("from-flag"));
+        assert_eq!(load_token(None, None).unwrap(), None);
+    }
+
+    #[test]
+    fn load_token_rejects_empty_and_missing_tokens() {
+        assert!(load_token(Some(" \n".into()), None).is_err());
+        let missing = std::env::temp_dir().join("mcp-screenshot-token-does-not-exist");
+        assert!(load_token(None, Some(missing)).is_err());
+    }
+}
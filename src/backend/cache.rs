@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use image::RgbaImage;
+use rmcp::ErrorData as McpError;
+
+/// Recent full-monitor captures, keyed by monitor ID, reused for `ttl` so a burst
+/// of region requests on one monitor costs a single capture.
+pub struct FrameCache {
+    ttl: Duration,
+    frames: Mutex<HashMap<u32, (Instant, Arc<RgbaImage>)>>,
+}
+
+impl FrameCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            frames: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Return the cached frame of `monitor_id` if it is younger than the TTL, else
+    /// run `capture` and cache its result. `fresh` skips the lookup but still
+    /// caches the new frame. The lock is not held while capturing.
+    pub fn get_or_capture(
+        &self,
+        monitor_id: u32,
+        fresh: bool,
+        capture: impl FnOnce() -> Result<RgbaImage, McpError>,
+    ) -> Result<Arc<RgbaImage>, McpError> {
+        if !fresh {
+            let frames = self.frames.lock().unwrap();
+            if let Some((taken, frame)) = frames.get(&monitor_id) {
+                let age = taken.elapsed();
+                if age < self.ttl {
+                    tracing::debug!(
+                        "Frame cache hit for monitor {monitor_id} ({} ms old)",
+                        age.as_millis()
+                    );
+                    return Ok(frame.clone());
+                }
+            }
+        }
+
+        tracing::debug!(
+            "Frame cache {} for monitor {monitor_id}",
+            if fresh { "bypass" } else { "miss" }
+        );
+        let taken = Instant::now();
+        let frame = Arc::new(capture()?);
+
+        let mut frames = self.frames.lock().unwrap();
+        frames.retain(|_, (t, _)| t.elapsed() < self.ttl);
+        frames.insert(monitor_id, (taken, frame.clone()));
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+
+    /// Capture a 1x1 frame holding the number of captures so far.
+    fn counting(captures: &Cell<u8>) -> impl FnOnce() -> Result<RgbaImage, McpError> + '_ {
+        move || {
+            captures.set(captures.get() + 1);
+            Ok(RgbaImage::from_pixel(
+                1,
+                1,
+                image::Rgba([captures.get(), 0, 0, 255]),
+            ))
+        }
+    }
+
+    fn id(frame: &RgbaImage) -> u8 {
+        frame.get_pixel(0, 0).0[0]
+    }
+
+    #[test]
+    fn frames_are_reused_within_the_ttl() {
+        let cache = FrameCache::new(Duration::from_secs(3600));
+        let captures = Cell::new(0);
+        let first = cache.get_or_capture(1, false, counting(&captures)).unwrap();
+        let second = cache.get_or_capture(1, false, counting(&captures)).unwrap();
+        assert_eq!((id(&first), id(&second)), (1, 1));
+        assert!(Arc::ptr_eq(&first, &second));
+
+        // Monitors are cached separately
+        let other = cache.get_or_capture(2, false, counting(&captures)).unwrap();
+        assert_eq!(id(&other), 2);
+    }
+
+    #[test]
+    fn frames_expire_after_the_ttl() {
+        let captures = Cell::new(0);
+        let disabled = FrameCache::new(Duration::ZERO);
+        disabled
+            .get_or_capture(1, false, counting(&captures))
+            .unwrap();
+        let again = disabled
+            .get_or_capture(1, false, counting(&captures))
+            .unwrap();
+        assert_eq!(id(&again), 2);
+
+        let cache = FrameCache::new(Duration::from_millis(20));
+        cache.get_or_capture(1, false, counting(&captures)).unwrap();
+        std::thread::sleep(Duration::from_millis(30));
+        let expired = cache.get_or_capture(1, false, counting(&captures)).unwrap();
+        assert_eq!(id(&expired), 4);
+    }
+
+    #[test]
+    fn fresh_bypasses_the_lookup_but_refills_the_cache() {
+        let cache = FrameCache::new(Duration::from_secs(3600));
+        let captures = Cell::new(0);
+        cache.get_or_capture(1, false, counting(&captures)).unwrap();
+        let fresh = cache.get_or_capture(1, true, counting(&captures)).unwrap();
+        assert_eq!(id(&fresh), 2);
+        let cached = cache.get_or_capture(1, false, counting(&captures)).unwrap();
+        assert!(Arc::ptr_eq(&fresh, &cached));
+    }
+
+    #[test]
+    fn failed_captures_are_not_cached() {
+        let cache = FrameCache::new(Duration::from_secs(3600));
+        let failed = cache.get_or_capture(1, false, || {
+            Err(McpError::internal_error("capture failed", None))
+        });
+        assert!(failed.is_err());
+        let captures = Cell::new(0);
+        let frame = cache.get_or_capture(1, false, counting(&captures)).unwrap();
+        assert_eq!(id(&frame), 1);
+    }
+}
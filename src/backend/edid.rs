@@ -0,0 +1,140 @@
+/// Identification fields from an EDID base block (VESA E-EDID 1.3/1.4).
+#[derive(Debug)]
+pub struct Edid {
+    /// Three-letter PNP manufacturer ID, e.g. `DEL`.
+    pub make: String,
+    /// Monitor name descriptor, else the product code in hex.
+    pub model: String,
+    /// Serial number descriptor, else the numeric serial when non-zero.
+    pub serial: Option<String>,
+    /// Screen size from the basic display parameters, in millimetres.
+    pub size_mm: Option<(u32, u32)>,
+}
+
+const HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+const DESCRIPTOR_OFFSETS: [usize; 4] = [54, 72, 90, 108];
+const TAG_SERIAL: u8 = 0xFF;
+const TAG_NAME: u8 = 0xFC;
+
+impl Edid {
+    /// Parse the 128-byte base block; extension blocks are ignored.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() < 128 || data[..8] != HEADER {
+            return None;
+        }
+
+        // Manufacturer ID: three 5-bit letters, big-endian, 'A' = 1
+        let id = u16::from_be_bytes([data[8], data[9]]);
+        let make: String = [10, 5, 0]
+            .iter()
+            .map(|shift| (b'A' - 1 + ((id >> shift) & 0x1F) as u8) as char)
+            .collect();
+        let product = u16::from_le_bytes([data[10], data[11]]);
+        let serial_number = u32::from_le_bytes([data[12], data[13], data[14], data[15]]);
+
+        let mut name = None;
+        let mut serial = None;
+        for offset in DESCRIPTOR_OFFSETS {
+            let d = &data[offset..offset + 18];
+            // Display descriptors start with a zero pixel clock
+            if d[..3] != [0, 0, 0] {
+                continue;
+            }
+            match d[3] {
+                TAG_NAME => name = descriptor_text(&d[5..]),
+                TAG_SERIAL => serial = descriptor_text(&d[5..]),
+                _ => {}
+            }
+        }
+
+        // Byte 21/22 hold the size in centimetres; zero means unknown or an aspect ratio
+        let size_mm =
+            (data[21] != 0 && data[22] != 0).then(|| (data[21] as u32 * 10, data[22] as u32 * 10));
+
+        Some(Edid {
+            make,
+            model: name.unwrap_or_else(|| format!("{product:04X}")),
+            serial: serial.or_else(|| (serial_number != 0).then(|| serial_number.to_string())),
+            size_mm,
+        })
+    }
+}
+
+/// Descriptor text: up to 13 bytes, ended by a newline and padded with spaces.
+fn descriptor_text(bytes: &[u8]) -> Option<String> {
+    let end = bytes
+        .iter()
+        .position(|&b| b == b'\n')
+        .unwrap_or(bytes.len());
+    let text = String::from_utf8_lossy(&bytes[..end]).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Complete base block in the layout of a Dell U2415: 1920x1200 preferred timing,
+    /// serial, name and range-limit descriptors, one extension announced, valid checksum.
+    const U2415: [u8; 128] = [
+        0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x10, 0xAC, 0xC4, 0xA0, 0x4C, 0x4C, 0x30,
+        0x39, 0x0C, 0x19, 0x01, 0x04, 0xA5, 0x34, 0x20, 0x78, 0x3A, 0xEE, 0x95, 0xA3, 0x54, 0x4C,
+        0x99, 0x26, 0x0F, 0x50, 0x54, 0xA5, 0x4B, 0x00, 0x71, 0x4F, 0x81, 0x80, 0xA9, 0x40, 0xD1,
+        0xC0, 0xD1, 0x00, 0x81, 0x40, 0x01, 0x01, 0x01, 0x01, 0x28, 0x3C, 0x80, 0xA0, 0x70, 0xB0,
+        0x23, 0x40, 0x30, 0x20, 0x36, 0x00, 0x06, 0x44, 0x21, 0x00, 0x00, 0x1A, 0x00, 0x00, 0x00,
+        0xFF, 0x00, 0x43, 0x46, 0x56, 0x39, 0x4E, 0x35, 0x35, 0x4C, 0x30, 0x37, 0x4C, 0x4C, 0x0A,
+        0x00, 0x00, 0x00, 0xFC, 0x00, 0x44, 0x45, 0x4C, 0x4C, 0x20, 0x55, 0x32, 0x34, 0x31, 0x35,
+        0x0A, 0x20, 0x20, 0x00, 0x00, 0x00, 0xFD, 0x00, 0x38, 0x4C, 0x1E, 0x51, 0x11, 0x00, 0x0A,
+        0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x01, 0x6D,
+    ];
+
+    #[test]
+    fn golden_block_is_parsed() {
+        assert_eq!(U2415.iter().fold(0u8, |sum, b| sum.wrapping_add(*b)), 0);
+        let edid = Edid::parse(&U2415).unwrap();
+        assert_eq!(edid.make, "DEL");
+        assert_eq!(edid.model, "DELL U2415");
+        assert_eq!(edid.serial.as_deref(), Some("CFV9N55L07LL"));
+        assert_eq!(edid.size_mm, Some((520, 320)));
+    }
+
+    #[test]
+    fn extension_blocks_are_ignored() {
+        let mut data = U2415.to_vec();
+        data.extend_from_slice(&[0x02; 128]);
+        assert_eq!(Edid::parse(&data).unwrap().model, "DELL U2415");
+    }
+
+    #[test]
+    fn missing_descriptors_fall_back_to_product_and_serial_numbers() {
+        let mut data = U2415;
+        // Turn the serial and name descriptors into dummy descriptors
+        data[72 + 3] = 0x10;
+        data[90 + 3] = 0x10;
+        data[21] = 0;
+        let edid = Edid::parse(&data).unwrap();
+        assert_eq!(edid.model, "A0C4");
+        assert_eq!(edid.serial.as_deref(), Some("959466572"));
+        assert_eq!(edid.size_mm, None);
+
+        data[12..16].fill(0);
+        assert_eq!(Edid::parse(&data).unwrap().serial, None);
+    }
+
+    #[test]
+    fn truncated_blocks_are_rejected() {
+        assert!(Edid::parse(&[]).is_none());
+        assert!(Edid::parse(&U2415[..8]).is_none());
+        assert!(Edid::parse(&U2415[..127]).is_none());
+    }
+
+    #[test]
+    fn bad_header_is_rejected() {
+        let mut data = U2415;
+        data[0] = 0xFF;
+        assert!(Edid::parse(&data).is_none());
+        let mut data = U2415;
+        data[7] = 0xFF;
+        assert!(Edid::parse(&data).is_none());
+    }
+}
@@ -0,0 +1,589 @@
+use std::ffi::c_ulong;
+use std::fs::{self, File};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+use image::RgbaImage;
+use rmcp::ErrorData as McpError;
+use rustix::ioctl::{self, BadOpcode, Getter};
+use rustix::mm::{self, MapFlags, ProtFlags};
+use serde::Serialize;
+
+use super::MonitorInfo;
+use super::pixel_format::{self, BitfieldLayout, Channel};
+
+// -- Kernel ABI (linux/fb.h) --
+
+const FBIOGET_VSCREENINFO: ioctl::RawOpcode = 0x4600;
+const FBIOGET_FSCREENINFO: ioctl::RawOpcode = 0x4602;
+
+const FB_TYPE_PACKED_PIXELS: u32 = 0;
+const FB_VISUAL_TRUECOLOR: u32 = 2;
+const FB_VISUAL_DIRECTCOLOR: u32 = 4;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct FbBitfield {
+    offset: u32,
+    length: u32,
+    msb_right: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct FbVarScreeninfo {
+    xres: u32,
+    yres: u32,
+    xres_virtual: u32,
+    yres_virtual: u32,
+    xoffset: u32,
+    yoffset: u32,
+    bits_per_pixel: u32,
+    grayscale: u32,
+    red: FbBitfield,
+    green: FbBitfield,
+    blue: FbBitfield,
+    transp: FbBitfield,
+    nonstd: u32,
+    activate: u32,
+    height: u32,
+    width: u32,
+    accel_flags: u32,
+    pixclock: u32,
+    left_margin: u32,
+    right_margin: u32,
+    upper_margin: u32,
+    lower_margin: u32,
+    hsync_len: u32,
+    vsync_len: u32,
+    sync: u32,
+    vmode: u32,
+    rotate: u32,
+    colorspace: u32,
+    reserved: [u32; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+struct FbFixScreeninfo {
+    id: [u8; 16],
+    smem_start: c_ulong,
+    smem_len: u32,
+    type_: u32,
+    type_aux: u32,
+    visual: u32,
+    xpanstep: u16,
+    ypanstep: u16,
+    ywrapstep: u16,
+    line_length: u32,
+    mmio_start: c_ulong,
+    mmio_len: u32,
+    accel: u32,
+    capabilities: u16,
+    reserved: [u16; 2],
+}
+
+impl FbVarScreeninfo {
+    /// Refresh rate from the video timings; `None` when the driver reports no pixel clock.
+    fn refresh_rate(&self) -> Option<f32> {
+        if self.pixclock == 0 {
+            return None;
+        }
+        let htotal = self.left_margin + self.xres + self.right_margin + self.hsync_len;
+        let vtotal = self.upper_margin + self.yres + self.lower_margin + self.vsync_len;
+        // pixclock is the pixel period in picoseconds
+        let frame_ps = self.pixclock as f64 * htotal as f64 * vtotal as f64;
+        Some((1e12 / frame_ps) as f32)
+    }
+}
+
+impl FbFixScreeninfo {
+    fn id(&self) -> String {
+        let len = self.id.iter().position(|&b| b == 0).unwrap_or(self.id.len());
+        String::from_utf8_lossy(&self.id[..len]).into_owned()
+    }
+}
+
+fn query_var(file: &File) -> std::io::Result<FbVarScreeninfo> {
+    // SAFETY: FBIOGET_VSCREENINFO writes exactly one fb_var_screeninfo.
+    let var = unsafe {
+        ioctl::ioctl(file, Getter::<BadOpcode<FBIOGET_VSCREENINFO>, FbVarScreeninfo>::new())
+    }?;
+    Ok(var)
+}
+
+fn query_fix(file: &File) -> std::io::Result<FbFixScreeninfo> {
+    // SAFETY: FBIOGET_FSCREENINFO writes exactly one fb_fix_screeninfo.
+    let fix = unsafe {
+        ioctl::ioctl(file, Getter::<BadOpcode<FBIOGET_FSCREENINFO>, FbFixScreeninfo>::new())
+    }?;
+    Ok(fix)
+}
+
+/// Translate the screen info into a pixel layout, rejecting modes that are not
+/// packed true-colour pixels.
+fn pixel_layout(var: &FbVarScreeninfo, fix: &FbFixScreeninfo) -> Result<BitfieldLayout, String> {
+    if fix.type_ != FB_TYPE_PACKED_PIXELS {
+        return Err(format!("Unsupported framebuffer type {} (only packed pixels)", fix.type_));
+    }
+    if fix.visual != FB_VISUAL_TRUECOLOR && fix.visual != FB_VISUAL_DIRECTCOLOR {
+        return Err(format!(
+            "Unsupported framebuffer visual {} (palette modes are not supported)",
+            fix.visual
+        ));
+    }
+    // grayscale > 1 holds a FOURCC code from the newer fbdev format API
+    if var.grayscale > 1 || var.nonstd != 0 {
+        return Err("Non-standard framebuffer pixel formats are not supported".to_string());
+    }
+
+    let channel = |bf: FbBitfield| -> Result<Channel, String> {
+        if bf.msb_right != 0 {
+            return Err("Framebuffers with MSB-right bitfields are not supported".to_string());
+        }
+        Ok(Channel {
+            offset: bf.offset,
+            length: bf.length,
+        })
+    };
+    Ok(BitfieldLayout {
+        bits_per_pixel: var.bits_per_pixel,
+        red: channel(var.red)?,
+        green: channel(var.green)?,
+        blue: channel(var.blue)?,
+        alpha: channel(var.transp)?,
+    })
+}
+
+// -- Framebuffer device --
+
+/// One open framebuffer device.
+struct Framebuffer {
+    path: PathBuf,
+    file: File,
+    /// Screen info read at open time; used when it cannot be re-queried.
+    var: FbVarScreeninfo,
+    fix: FbFixScreeninfo,
+}
+
+impl Framebuffer {
+    fn open(path: &Path) -> std::io::Result<Self> {
+        let file = File::open(path)?;
+        let var = query_var(&file)?;
+        let fix = query_fix(&file)?;
+        Ok(Self::new(path.to_path_buf(), file, var, fix))
+    }
+
+    /// Wrap an open file with known screen info. Capture only needs mmap or
+    /// positioned reads on `file`, so a regular file can stand in for the device.
+    fn new(path: PathBuf, file: File, var: FbVarScreeninfo, fix: FbFixScreeninfo) -> Self {
+        Framebuffer {
+            path,
+            file,
+            var,
+            fix,
+        }
+    }
+
+    fn name(&self) -> String {
+        let node = self
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match self.fix.id() {
+            id if id.is_empty() => node,
+            id => format!("{node} ({id})"),
+        }
+    }
+
+    /// Current screen info; the visible area moves when the driver pans between buffers.
+    fn current_var(&self) -> FbVarScreeninfo {
+        query_var(&self.file).unwrap_or(self.var)
+    }
+
+    fn pitch(&self, var: &FbVarScreeninfo) -> u32 {
+        if self.fix.line_length != 0 {
+            self.fix.line_length
+        } else {
+            var.xres_virtual * var.bits_per_pixel.div_ceil(8)
+        }
+    }
+
+    fn capture(&self) -> Result<RgbaImage, McpError> {
+        let var = self.current_var();
+        let layout = pixel_layout(&var, &self.fix).map_err(|e| McpError::internal_error(e, None))?;
+        let (width, height) = (var.xres, var.yres);
+        if width == 0 || height == 0 {
+            return Err(McpError::internal_error("Framebuffer has no visible area", None));
+        }
+
+        let pitch = self.pitch(&var);
+        let bytes_per_pixel = var.bits_per_pixel.div_ceil(8) as usize;
+        let start = var.yoffset as usize * pitch as usize + var.xoffset as usize * bytes_per_pixel;
+        let len = (height as usize - 1) * pitch as usize + width as usize * bytes_per_pixel;
+        let raw = self.read(start, len)?;
+
+        let rgba_data = match layout.fourcc() {
+            Some(format) => pixel_format::convert_to_rgba(&raw, width, height, pitch, format),
+            None => pixel_format::convert_bitfields(&raw, width, height, pitch, &layout),
+        }
+        .map_err(|e| McpError::internal_error(e, None))?;
+
+        RgbaImage::from_raw(width, height, rgba_data).ok_or_else(|| {
+            McpError::internal_error("Failed to create image from pixel data", None)
+        })
+    }
+
+    /// Read `len` bytes at `start` through mmap, falling back to positioned reads
+    /// for drivers without mmap support.
+    fn read(&self, start: usize, len: usize) -> Result<Vec<u8>, McpError> {
+        let end = start + len;
+        let mut limit = self.fix.smem_len as usize;
+        // Mapping past the end of a regular file would fault on access
+        if let Ok(meta) = self.file.metadata()
+            && meta.is_file()
+        {
+            limit = meta.len() as usize;
+        }
+        if limit != 0 && end > limit {
+            return Err(McpError::internal_error(
+                format!("Visible area ends at byte {end}, past the {limit}-byte framebuffer"),
+                None,
+            ));
+        }
+
+        // SAFETY: the mapping covers [0, end), which lies within the device memory
+        // (or file) checked above. We copy out of it and unmap immediately.
+        let mapped = unsafe {
+            mm::mmap(ptr::null_mut(), end, ProtFlags::READ, MapFlags::SHARED, &self.file, 0).map(
+                |ptr| {
+                    let slice = std::slice::from_raw_parts(ptr.cast::<u8>(), end);
+                    let buf = slice[start..].to_vec();
+                    let _ = mm::munmap(ptr, end);
+                    buf
+                },
+            )
+        };
+
+        match mapped {
+            Ok(buf) => Ok(buf),
+            Err(e) => {
+                tracing::debug!("mmap of {} failed ({e}), using read", self.path.display());
+                let mut buf = vec![0; len];
+                self.file.read_exact_at(&mut buf, start as u64).map_err(|e| {
+                    McpError::internal_error(format!("Failed to read framebuffer: {e}"), None)
+                })?;
+                Ok(buf)
+            }
+        }
+    }
+}
+
+// -- fbdev backend --
+
+pub struct FbdevBackend {
+    framebuffers: Vec<Framebuffer>,
+}
+
+impl FbdevBackend {
+    /// Open every `/dev/fb*` device whose screen info can be read and whose
+    /// pixel layout is supported.
+    pub fn open() -> Result<Self, Box<dyn std::error::Error>> {
+        let mut framebuffers = Vec::new();
+        for path in Self::device_paths()? {
+            match Framebuffer::open(&path) {
+                Ok(fb) => match pixel_layout(&fb.var, &fb.fix) {
+                    Ok(layout) => {
+                        tracing::debug!(
+                            "{}: {}x{} {layout}",
+                            path.display(),
+                            fb.var.xres,
+                            fb.var.yres
+                        );
+                        framebuffers.push(fb);
+                    }
+                    Err(e) => tracing::debug!("{}: {e}", path.display()),
+                },
+                Err(e) => tracing::debug!("Cannot open {}: {e}", path.display()),
+            }
+        }
+
+        if framebuffers.is_empty() {
+            return Err("No usable framebuffer device found. \
+                        Ensure /dev/fb* exists and is readable (e.g. add the user to the video group)"
+                .into());
+        }
+        tracing::info!("fbdev: using {} framebuffer(s)", framebuffers.len());
+        Ok(FbdevBackend { framebuffers })
+    }
+
+    /// `/dev/fbN` device nodes in numeric order.
+    fn device_paths() -> std::io::Result<Vec<PathBuf>> {
+        let mut numbered: Vec<(u32, PathBuf)> = fs::read_dir("/dev")?
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let n = e.file_name().to_str()?.strip_prefix("fb")?.parse().ok()?;
+                Some((n, e.path()))
+            })
+            .collect();
+        numbered.sort();
+        Ok(numbered.into_iter().map(|(_, path)| path).collect())
+    }
+
+    pub fn capture_monitor(&self, monitor_id: Option<u32>) -> Result<RgbaImage, McpError> {
+        let fb = match monitor_id {
+            Some(id) => self.framebuffers.get(id as usize).ok_or_else(|| {
+                McpError::invalid_params(format!("Monitor index {id} out of range"), None)
+            })?,
+            None => self.framebuffers.first().ok_or_else(|| {
+                McpError::internal_error("No framebuffers", None)
+            })?,
+        };
+
+        fb.capture()
+    }
+
+    /// Framebuffers are independent screens, so they are placed side by side in
+    /// device order.
+    pub fn list_monitors(&self) -> Result<Vec<MonitorInfo>, McpError> {
+        let mut right_edge = 0i32;
+        Ok(self
+            .framebuffers
+            .iter()
+            .enumerate()
+            .map(|(i, fb)| {
+                let var = fb.current_var();
+                let x = right_edge;
+                right_edge = right_edge.saturating_add(var.xres.min(i32::MAX as u32) as i32);
+                MonitorInfo {
+                    id: i as u32,
+                    name: fb.name(),
+                    x,
+                    y: 0,
+                    width: var.xres,
+                    height: var.yres,
+                    is_primary: i == 0,
+                    refresh_rate: var.refresh_rate(),
+                    // FB_ROTATE_CW/UD/CCW, turned into counter-clockwise degrees
+                    rotation: [0, 270, 180, 90].get(var.rotate as usize).copied(),
+                    scale_factor: Some(1.0),
+                    // The kernel reports unknown sizes as 0 or as -1 cast to u32
+                    width_mm: Some(var.width).filter(|&mm| mm != 0 && mm != u32::MAX),
+                    height_mm: Some(var.height).filter(|&mm| mm != 0 && mm != u32::MAX),
+                    ..Default::default()
+                }
+            })
+            .collect())
+    }
+}
+
+// -- Diagnostics --
+
+#[derive(Serialize)]
+pub struct FbdevDiagnostics {
+    pub path: String,
+    /// Why the device could not be opened or its screen info read.
+    pub error: Option<String>,
+    pub id: Option<String>,
+    pub resolution: Option<String>,
+    pub layout: Option<String>,
+    /// Why the pixel layout cannot be converted.
+    pub layout_error: Option<String>,
+}
+
+impl FbdevBackend {
+    /// Describe every `/dev/fb*` device the way `open` sees it.
+    pub fn diagnose() -> Vec<FbdevDiagnostics> {
+        let paths = Self::device_paths().unwrap_or_default();
+        paths
+            .iter()
+            .map(|path| {
+                let mut report = FbdevDiagnostics {
+                    path: path.display().to_string(),
+                    error: None,
+                    id: None,
+                    resolution: None,
+                    layout: None,
+                    layout_error: None,
+                };
+                match Framebuffer::open(path) {
+                    Ok(fb) => {
+                        report.id = Some(fb.fix.id());
+                        report.resolution = Some(format!("{}x{}", fb.var.xres, fb.var.yres));
+                        match pixel_layout(&fb.var, &fb.fix) {
+                            Ok(layout) => report.layout = Some(layout.to_string()),
+                            Err(e) => report.layout_error = Some(e),
+                        }
+                    }
+                    Err(e) => report.error = Some(e.to_string()),
+                }
+                report
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bitfield(offset: u32, length: u32) -> FbBitfield {
+        FbBitfield {
+            offset,
+            length,
+            msb_right: 0,
+        }
+    }
+
+    /// Screen info for a `width`x`height` true-colour mode with no panning room.
+    fn screen(
+        width: u32,
+        height: u32,
+        bpp: u32,
+        rgb: [(u32, u32); 3],
+        line_length: u32,
+    ) -> (FbVarScreeninfo, FbFixScreeninfo) {
+        let var = FbVarScreeninfo {
+            xres: width,
+            yres: height,
+            xres_virtual: width,
+            yres_virtual: height,
+            bits_per_pixel: bpp,
+            red: bitfield(rgb[0].0, rgb[0].1),
+            green: bitfield(rgb[1].0, rgb[1].1),
+            blue: bitfield(rgb[2].0, rgb[2].1),
+            ..Default::default()
+        };
+        let fix = FbFixScreeninfo {
+            type_: FB_TYPE_PACKED_PIXELS,
+            visual: FB_VISUAL_TRUECOLOR,
+            line_length,
+            ..Default::default()
+        };
+        (var, fix)
+    }
+
+    /// A framebuffer backed by a temporary regular file holding `contents`.
+    fn framebuffer(
+        name: &str,
+        contents: &[u8],
+        var: FbVarScreeninfo,
+        fix: FbFixScreeninfo,
+    ) -> Framebuffer {
+        let path = std::env::temp_dir().join(format!("fbdev-test-{}-{name}", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        let file = File::open(&path).unwrap();
+        // The open handle keeps the contents readable
+        fs::remove_file(&path).unwrap();
+        Framebuffer::new(path, file, var, fix)
+    }
+
+    fn pixels(image: &RgbaImage) -> Vec<[u8; 4]> {
+        image.pixels().map(|p| p.0).collect()
+    }
+
+    #[test]
+    fn rgb565() {
+        let (var, fix) = screen(2, 2, 16, [(11, 5), (5, 6), (0, 5)], 0);
+        let data = [0xF800u16, 0x07E0, 0x001F, 0xFFFF]
+            .iter()
+            .flat_map(|px| px.to_le_bytes())
+            .collect::<Vec<_>>();
+        let image = framebuffer("rgb565", &data, var, fix).capture().unwrap();
+        assert_eq!(image.dimensions(), (2, 2));
+        assert_eq!(
+            pixels(&image),
+            [
+                [255, 0, 0, 255],
+                [0, 255, 0, 255],
+                [0, 0, 255, 255],
+                [255, 255, 255, 255]
+            ]
+        );
+    }
+
+    #[test]
+    fn xrgb8888_uses_the_line_length_as_pitch() {
+        // Two pixels per row, padded to 16 bytes with junk the capture must skip
+        let (var, fix) = screen(2, 2, 32, [(16, 8), (8, 8), (0, 8)], 16);
+        let mut data = vec![0xAA; 32];
+        data[0..8].copy_from_slice(&[0x30, 0x20, 0x10, 0, 0x60, 0x50, 0x40, 0]);
+        data[16..24].copy_from_slice(&[0x90, 0x80, 0x70, 0, 0xC0, 0xB0, 0xA0, 0]);
+        let image = framebuffer("xrgb8888", &data, var, fix).capture().unwrap();
+        assert_eq!(
+            pixels(&image),
+            [
+                [0x10, 0x20, 0x30, 255],
+                [0x40, 0x50, 0x60, 255],
+                [0x70, 0x80, 0x90, 255],
+                [0xA0, 0xB0, 0xC0, 255]
+            ]
+        );
+    }
+
+    #[test]
+    fn panning_offsets_the_visible_area() {
+        // A 4x4 virtual screen showing the 2x2 window at (1, 2)
+        let (mut var, fix) = screen(2, 2, 32, [(16, 8), (8, 8), (0, 8)], 0);
+        var.xres_virtual = 4;
+        var.yres_virtual = 4;
+        var.xoffset = 1;
+        var.yoffset = 2;
+        // Each pixel's red channel holds its virtual-screen index
+        let data = (0..16u8).flat_map(|i| [0, 0, i, 0]).collect::<Vec<_>>();
+        let image = framebuffer("panned", &data, var, fix).capture().unwrap();
+        let reds = pixels(&image).iter().map(|p| p[0]).collect::<Vec<_>>();
+        assert_eq!(reds, [9, 10, 13, 14]);
+    }
+
+    #[test]
+    fn layouts_without_a_fourcc_use_bitfields() {
+        // RGB332 (RRRGGGBB) has no DRM format, so it goes through convert_bitfields
+        let (var, fix) = screen(3, 1, 8, [(5, 3), (2, 3), (0, 2)], 0);
+        assert_eq!(pixel_layout(&var, &fix).unwrap().fourcc(), None);
+        let data = [0xE0, 0x1C, 0x03];
+        let image = framebuffer("rgb332", &data, var, fix).capture().unwrap();
+        assert_eq!(
+            pixels(&image),
+            [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]]
+        );
+    }
+
+    #[test]
+    fn framebuffers_are_placed_side_by_side() {
+        let (var, fix) = screen(2, 2, 16, [(11, 5), (5, 6), (0, 5)], 0);
+        let (wide, _) = screen(3, 1, 16, [(11, 5), (5, 6), (0, 5)], 0);
+        let backend = FbdevBackend {
+            framebuffers: vec![
+                framebuffer("fb0", &[0; 8], var, fix),
+                framebuffer("fb1", &[0; 6], wide, fix),
+                framebuffer("fb2", &[0; 8], var, fix),
+            ],
+        };
+        let positions = backend
+            .list_monitors()
+            .unwrap()
+            .iter()
+            .map(|m| (m.x, m.y))
+            .collect::<Vec<_>>();
+        assert_eq!(positions, [(0, 0), (2, 0), (5, 0)]);
+    }
+
+    #[test]
+    fn reads_past_the_end_are_rejected() {
+        // Two rows of 2x2 XRGB8888 need 16 bytes; the file holds 12
+        let (var, fix) = screen(2, 2, 32, [(16, 8), (8, 8), (0, 8)], 0);
+        let fb = framebuffer("short", &[0; 12], var, fix);
+        let err = fb.capture().unwrap_err();
+        assert!(
+            err.message.contains("past the 12-byte framebuffer"),
+            "{}",
+            err.message
+        );
+        let err = fb.read(8, 8).unwrap_err();
+        assert!(err.message.contains("ends at byte 16"), "{}", err.message);
+        assert_eq!(fb.read(4, 8).unwrap(), [0; 8]);
+    }
+}
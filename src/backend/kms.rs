@@ -1,16 +1,26 @@
+use std::collections::HashMap;
 use std::fs::{self, File, OpenOptions};
 use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
+use std::ops::Range;
+use std::path::PathBuf;
 use std::ptr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 
-use drm::control::{connector, crtc, framebuffer, Device as ControlDevice};
-use drm::Device;
+use drm::control::{
+    connector, crtc, framebuffer, plane, property, Device as ControlDevice,
+    GetPlanarFramebufferError, Mode, ModeFlags,
+};
+use drm::{ClientCapability, Device};
 use drm_fourcc::{DrmFourcc, DrmModifier};
-use image::RgbaImage;
+use image::{imageops, Rgba, RgbaImage};
 use rmcp::ErrorData as McpError;
 use rustix::mm::{self, MapFlags, ProtFlags};
+use serde::Serialize;
 
-use super::pixel_format;
-use super::MonitorInfo;
+use super::pixel_format::{self, Plane};
+use super::edid::Edid;
+use super::tiling::{self, Tiling};
+use super::{MonitorInfo, Rect};
 
 // -- DRM Card wrapper --
 
@@ -28,202 +38,954 @@ impl ControlDevice for Card {}
 impl Card {
     fn open(path: &str) -> std::io::Result<Self> {
         let file = OpenOptions::new().read(true).write(true).open(path)?;
-        Ok(Card(file))
+        let card = Card(file);
+        // Atomic exposes the SRC_*/CRTC_* plane properties and implies universal
+        // planes; without either only overlay planes are listed, not primary and cursor
+        if let Err(e) = card.set_client_capability(ClientCapability::Atomic, true) {
+            tracing::debug!("{path}: atomic plane properties unavailable: {e}");
+            if let Err(e) = card.set_client_capability(ClientCapability::UniversalPlanes, true) {
+                tracing::debug!("{path}: universal planes unavailable: {e}");
+            }
+        }
+        Ok(card)
+    }
+
+    /// Property values of a KMS object by name. Enum properties also carry the
+    /// name of their current value.
+    fn properties<T: drm::control::ResourceHandle>(
+        &self,
+        handle: T,
+    ) -> std::io::Result<HashMap<String, (property::RawValue, Option<String>)>> {
+        let mut props = HashMap::new();
+        for (&prop, &raw) in &self.get_properties(handle)? {
+            let info = self.get_property(prop)?;
+            let enum_name = match info.value_type() {
+                property::ValueType::Enum(values) => values
+                    .get_value_from_raw_value(raw)
+                    .map(|v| v.name().to_string_lossy().into_owned()),
+                _ => None,
+            };
+            props.insert(info.name().to_string_lossy().into_owned(), (raw, enum_name));
+        }
+        Ok(props)
+    }
+}
+
+/// GEM handles returned by GET_FB/GET_FB2. They hold a reference to the buffer
+/// object, so they are closed on drop whichever way the capture ends.
+struct GemHandles<'a> {
+    card: &'a Card,
+    handles: Vec<drm::buffer::Handle>,
+}
+
+impl<'a> GemHandles<'a> {
+    fn new(card: &'a Card, handles: impl IntoIterator<Item = drm::buffer::Handle>) -> Self {
+        let mut distinct = Vec::new();
+        for handle in handles {
+            if !distinct.contains(&handle) {
+                distinct.push(handle);
+            }
+        }
+        GemHandles {
+            card,
+            handles: distinct,
+        }
+    }
+}
+
+impl Drop for GemHandles<'_> {
+    fn drop(&mut self) {
+        for &handle in &self.handles {
+            let _ = self.card.close_buffer(handle);
+        }
+    }
+}
+
+// -- Planes: what each CRTC scans out, and where --
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum PlaneKind {
+    Primary,
+    Overlay,
+    Cursor,
+}
+
+impl PlaneKind {
+    fn name(self) -> &'static str {
+        match self {
+            PlaneKind::Primary => "primary",
+            PlaneKind::Overlay => "overlay",
+            PlaneKind::Cursor => "cursor",
+        }
+    }
+}
+
+/// How a plane's pixel alpha combines with what is below it
+/// (the "pixel blend mode" plane property).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlendMode {
+    /// Pixel alpha is ignored.
+    Opaque,
+    /// Colour channels are already multiplied by alpha (the kernel default).
+    Premultiplied,
+    /// Colour channels are straight; alpha is applied during blending.
+    Coverage,
+}
+
+/// One plane scanning out on a CRTC, with its placement from the plane properties.
+struct PlaneState {
+    handle: plane::Handle,
+    kind: PlaneKind,
+    fb_handle: framebuffer::Handle,
+    zpos: Option<u64>,
+    /// Source rectangle in framebuffer pixels; `None` without the atomic SRC_* properties.
+    src: Option<Rect>,
+    /// Destination rectangle in CRTC coordinates; `None` without the atomic CRTC_* properties.
+    dst: Option<Rect>,
+    /// Plane-wide opacity, 0..=0xFFFF.
+    alpha: u16,
+    blend: BlendMode,
+    /// Counter-clockwise rotation in degrees, from the "rotation" property.
+    rotation: Option<u32>,
+    /// Mirror the source left to right (DRM_MODE_REFLECT_X).
+    reflect_x: bool,
+    /// Mirror the source top to bottom (DRM_MODE_REFLECT_Y).
+    reflect_y: bool,
+}
+
+impl PlaneState {
+    /// Source rectangle (framebuffer pixels) and destination rectangle (CRTC
+    /// coordinates) of the plane on a CRTC scanning out `viewport` of its
+    /// framebuffer. Without the atomic properties only the primary plane can be
+    /// placed, as the CRTC viewport; other planes give `None` and are left out.
+    fn placement(&self, viewport: Rect) -> Option<(Rect, Rect)> {
+        let full_crtc = Rect {
+            x: 0,
+            y: 0,
+            ..viewport
+        };
+        match (self.src, self.dst, self.kind) {
+            (Some(src), Some(dst), _) => Some((src, dst)),
+            (src, dst, PlaneKind::Primary) => {
+                Some((src.unwrap_or(viewport), dst.unwrap_or(full_crtc)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the source is rotated or reflected on its way to the destination.
+    fn is_transformed(&self) -> bool {
+        self.rotation.unwrap_or(0) != 0 || self.reflect_x || self.reflect_y
+    }
+
+    /// Reflect, then rotate, the plane's source pixels as the display engine does
+    /// between the source and destination rectangles (see `drm_rect_rotate`).
+    fn orient(&self, mut img: RgbaImage) -> RgbaImage {
+        if self.reflect_x {
+            imageops::flip_horizontal_in_place(&mut img);
+        }
+        if self.reflect_y {
+            imageops::flip_vertical_in_place(&mut img);
+        }
+        // The image crate rotates clockwise
+        match self.rotation {
+            Some(90) => imageops::rotate270(&img),
+            Some(180) => imageops::rotate180(&img),
+            Some(270) => imageops::rotate90(&img),
+            _ => img,
+        }
     }
 }
 
 // -- Active output: connector -> encoder -> crtc chain --
 
+#[derive(Clone)]
 struct ActiveOutput {
+    /// Monitor ID, derived from the card and connector (see `monitor_id`).
+    id: u32,
+    /// Index into `KmsBackend::cards`.
+    card: usize,
+    connector_handle: connector::Handle,
     connector_name: String,
     crtc_handle: crtc::Handle,
+    /// Top-left of the CRTC's viewport within its framebuffer.
+    crtc_x: u32,
+    crtc_y: u32,
+    /// Position in global desktop coordinates, assigned by `lay_out`.
+    x: i32,
+    y: i32,
     width: u32,
     height: u32,
+    mode: Mode,
     fb_handle: framebuffer::Handle,
 }
 
+/// How far a connector resolves along connector -> encoder -> CRTC -> mode/framebuffer.
+struct ConnectorChain {
+    handle: connector::Handle,
+    name: String,
+    interface: connector::Interface,
+    interface_id: u32,
+    state: connector::State,
+    crtc_handle: Option<crtc::Handle>,
+    position: (u32, u32),
+    mode: Option<Mode>,
+    fb_handle: Option<framebuffer::Handle>,
+}
+
+impl ActiveOutput {
+    /// Whether a re-probe found a different output layout, ignoring page flips.
+    fn same_layout(&self, other: &ActiveOutput) -> bool {
+        self.id == other.id
+            && self.crtc_handle == other.crtc_handle
+            && (self.x, self.y, self.width, self.height)
+                == (other.x, other.y, other.width, other.height)
+    }
+
+    /// The whole output, in output-relative coordinates.
+    fn full_area(&self) -> Rect {
+        Rect {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Whether `name` picks this output: either the connector name (`HDMI-A-1`)
+    /// or the sysfs-style name including the card (`card0-HDMI-A-1`).
+    fn matches_name(&self, name: &str, card: &KmsCard) -> bool {
+        name == self.connector_name
+            || name == format!("card{}-{}", card.number, self.connector_name)
+    }
+}
+
+impl ConnectorChain {
+    /// The output to capture, if the chain is complete. Its desktop position is
+    /// left at the origin until `lay_out` runs.
+    fn active_output(&self, card: usize, card_number: u32) -> Option<ActiveOutput> {
+        let mode = self.mode?;
+        let (w, h) = mode.size();
+        Some(ActiveOutput {
+            id: monitor_id(card_number, self.interface, self.interface_id),
+            card,
+            connector_handle: self.handle,
+            connector_name: self.name.clone(),
+            crtc_handle: self.crtc_handle?,
+            crtc_x: self.position.0,
+            crtc_y: self.position.1,
+            x: 0,
+            y: 0,
+            width: w as u32,
+            height: h as u32,
+            mode,
+            fb_handle: self.fb_handle?,
+        })
+    }
+}
+
+/// Refresh rate of a mode in Hz, from its pixel clock and timings like the kernel's
+/// `drm_mode_vrefresh`, so 59.94 Hz modes are not rounded to 60.
+fn refresh_rate(mode: &Mode) -> f32 {
+    let (_, _, htotal) = mode.hsync();
+    let (_, _, vtotal) = mode.vsync();
+    if htotal == 0 || vtotal == 0 {
+        return mode.vrefresh() as f32;
+    }
+    let mut hz = mode.clock() as f64 * 1000.0 / (htotal as f64 * vtotal as f64);
+    let flags = mode.flags();
+    if flags.contains(ModeFlags::INTERLACE) {
+        hz *= 2.0;
+    }
+    if flags.contains(ModeFlags::DBLSCAN) {
+        hz /= 2.0;
+    }
+    hz as f32
+}
+
+/// Monitor ID for a connector, stable across reboots, hotplugs and probe order:
+/// `card * 10000 + connector type * 100 + connector type index`, so `card0`
+/// `HDMI-A-1` is 1101 and `card1` `DP-2` is 11002.
+fn monitor_id(card_number: u32, interface: connector::Interface, interface_id: u32) -> u32 {
+    card_number * 10_000 + u32::from(interface) % 100 * 100 + interface_id % 100
+}
+
+/// Assign desktop positions. Outputs scanning out the same framebuffer share a
+/// desktop and keep their CRTC x/y within it; each distinct framebuffer (and so
+/// each card) is placed to the right of the ones before it.
+fn lay_out(outputs: &mut [ActiveOutput]) {
+    let mut surfaces: Vec<((usize, framebuffer::Handle), i32)> = Vec::new();
+    let mut right_edge = 0i64;
+    for output in outputs.iter_mut() {
+        let key = (output.card, output.fb_handle);
+        let origin = match surfaces.iter().find(|(k, _)| *k == key) {
+            Some((_, origin)) => *origin,
+            None => {
+                let origin = right_edge.min(i32::MAX as i64) as i32;
+                surfaces.push((key, origin));
+                origin
+            }
+        };
+        output.x = origin.saturating_add(output.crtc_x as i32);
+        output.y = output.crtc_y as i32;
+        right_edge = right_edge.max(output.x as i64 + output.width as i64);
+    }
+}
+
 // -- KMS backend --
 
-pub struct KmsBackend {
+/// One opened DRI card.
+struct KmsCard {
     card: Card,
-    outputs: Vec<ActiveOutput>,
+    path: String,
+    /// N in `/dev/dri/cardN`.
+    number: u32,
+}
+
+pub struct KmsBackend {
+    cards: Vec<KmsCard>,
+    /// Connector name from `--primary-output`.
+    primary: Option<String>,
+    outputs: Mutex<Outputs>,
+}
+
+/// Active outputs of every card as of the last probe.
+#[derive(Default)]
+struct Outputs {
+    active: Vec<ActiveOutput>,
+    /// Bumped whenever a probe finds a different set of outputs, modes or positions.
+    generation: u64,
+}
+
+impl Outputs {
+    fn update(&mut self, probed: Vec<ActiveOutput>) -> bool {
+        let changed = probed.len() != self.active.len()
+            || probed
+                .iter()
+                .zip(&self.active)
+                .any(|(new, old)| !new.same_layout(old));
+        self.active = probed;
+        if changed {
+            self.generation += 1;
+        }
+        changed
+    }
 }
 
 impl KmsBackend {
-    /// Open the first DRI card with connected outputs.
+    /// Open every DRI card and collect the active outputs of all of them.
+    /// `primary` names the connector to report as the primary monitor.
     /// Requires CAP_SYS_ADMIN for GET_FB/GET_FB2 ioctls.
-    pub fn open() -> Result<Self, Box<dyn std::error::Error>> {
-        let mut entries: Vec<_> = fs::read_dir("/dev/dri")?
+    pub fn open(primary: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut cards = Vec::new();
+        for (i, path) in Self::card_paths()?.into_iter().enumerate() {
+            let path = path.to_string_lossy().into_owned();
+            let card = match Card::open(&path) {
+                Ok(c) => c,
+                Err(e) => {
+                    tracing::debug!("Cannot open {path}: {e}");
+                    continue;
+                }
+            };
+            let number = path
+                .strip_prefix("/dev/dri/card")
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(i as u32);
+            cards.push(KmsCard { card, path, number });
+        }
+
+        let backend = KmsBackend {
+            cards,
+            primary: primary.filter(|name| !name.is_empty()).map(str::to_owned),
+            outputs: Default::default(),
+        };
+        let outputs = backend.probe_outputs(&[]);
+        if outputs.is_empty() {
+            return Err("No DRI card with active outputs found. \
+                        Ensure /dev/dri/card* exists and the process has CAP_SYS_ADMIN \
+                        (try: sudo setcap cap_sys_admin+ep <binary>)"
+                .into());
+        }
+        for (i, card) in backend.cards.iter().enumerate() {
+            let count = outputs.iter().filter(|o| o.card == i).count();
+            if count > 0 {
+                tracing::info!("KMS: using {} with {count} active output(s)", card.path);
+            }
+        }
+        if let Some(primary) = &backend.primary
+            && !outputs
+                .iter()
+                .any(|o| o.matches_name(primary, &backend.cards[o.card]))
+        {
+            tracing::warn!("Primary output {primary} is not active; using the first output");
+        }
+        backend.outputs().update(outputs);
+        Ok(backend)
+    }
+
+    /// `/dev/dri/card*` device nodes in name order.
+    fn card_paths() -> std::io::Result<Vec<PathBuf>> {
+        let mut paths: Vec<_> = fs::read_dir("/dev/dri")?
             .filter_map(|e| e.ok())
             .filter(|e| {
                 e.file_name()
                     .to_str()
                     .is_some_and(|n| n.starts_with("card"))
             })
+            .map(|e| e.path())
             .collect();
-        entries.sort_by_key(|e| e.file_name());
-
-        for entry in &entries {
-            let path = entry.path();
-            let path_str = path.to_string_lossy();
-            let card = match Card::open(&path_str) {
-                Ok(c) => c,
-                Err(e) => {
-                    tracing::debug!("Cannot open {path_str}: {e}");
-                    continue;
-                }
-            };
+        paths.sort();
+        Ok(paths)
+    }
 
-            match Self::probe_outputs(&card) {
-                Ok(outputs) if !outputs.is_empty() => {
-                    tracing::info!(
-                        "KMS: using {path_str} with {} active output(s)",
-                        outputs.len()
-                    );
-                    return Ok(KmsBackend { card, outputs });
-                }
-                Ok(_) => {
-                    tracing::debug!("{path_str}: no active outputs");
+    /// Active outputs of every card, laid out on the desktop. A card that fails
+    /// to probe keeps its outputs from `previous`: a transient failure is not an unplug.
+    fn probe_outputs(&self, previous: &[ActiveOutput]) -> Vec<ActiveOutput> {
+        let mut outputs = Vec::new();
+        for (i, card) in self.cards.iter().enumerate() {
+            match Self::probe_card(card, i) {
+                Ok(found) if found.is_empty() => {
+                    tracing::debug!("{}: no active outputs", card.path);
                 }
+                Ok(found) => outputs.extend(found),
                 Err(e) => {
-                    tracing::debug!("{path_str}: probe failed: {e}");
+                    tracing::debug!("{}: probe failed, keeping its last outputs: {e}", card.path);
+                    outputs.extend(previous.iter().filter(|o| o.card == i).cloned());
                 }
             }
         }
-
-        Err("No DRI card with active outputs found. \
-             Ensure /dev/dri/card* exists and the process has CAP_SYS_ADMIN \
-             (try: sudo setcap cap_sys_admin+ep <binary>)"
-            .into())
+        lay_out(&mut outputs);
+        outputs
     }
 
-    fn probe_outputs(card: &Card) -> Result<Vec<ActiveOutput>, Box<dyn std::error::Error>> {
-        let res = card.resource_handles()?;
+    fn probe_card(
+        card: &KmsCard,
+        index: usize,
+    ) -> Result<Vec<ActiveOutput>, Box<dyn std::error::Error>> {
+        let res = card.card.resource_handles()?;
         let mut outputs = Vec::new();
 
         for &conn_h in res.connectors() {
-            let conn = card.get_connector(conn_h, false)?;
-            if conn.state() != connector::State::Connected {
-                continue;
+            let chain = Self::connector_chain(&card.card, conn_h)?;
+            if let Some(output) = chain.active_output(index, card.number) {
+                outputs.push(output);
             }
+        }
 
-            let enc_h = match conn.current_encoder() {
-                Some(h) => h,
-                None => continue,
-            };
-            let enc = card.get_encoder(enc_h)?;
-            let crtc_h = match enc.crtc() {
-                Some(h) => h,
-                None => continue,
-            };
-            let crtc_info = card.get_crtc(crtc_h)?;
-            let mode = match crtc_info.mode() {
-                Some(m) => m,
-                None => continue,
-            };
-            let fb_h = match crtc_info.framebuffer() {
-                Some(h) => h,
-                None => continue,
-            };
+        Ok(outputs)
+    }
 
-            let (w, h) = mode.size();
-            outputs.push(ActiveOutput {
-                connector_name: format!("{}", conn),
-                crtc_handle: crtc_h,
-                width: w as u32,
-                height: h as u32,
-                fb_handle: fb_h,
-            });
+    /// Follow a connector through its encoder and CRTC as far as the chain is set up.
+    /// Disconnected connectors are not followed.
+    fn connector_chain(
+        card: &Card,
+        conn_h: connector::Handle,
+    ) -> Result<ConnectorChain, Box<dyn std::error::Error>> {
+        let conn = card.get_connector(conn_h, false)?;
+        let mut chain = ConnectorChain {
+            handle: conn_h,
+            name: format!("{}", conn),
+            interface: conn.interface(),
+            interface_id: conn.interface_id(),
+            state: conn.state(),
+            crtc_handle: None,
+            position: (0, 0),
+            mode: None,
+            fb_handle: None,
+        };
+        if conn.state() != connector::State::Connected {
+            return Ok(chain);
         }
 
-        Ok(outputs)
+        let Some(enc_h) = conn.current_encoder() else {
+            return Ok(chain);
+        };
+        let enc = card.get_encoder(enc_h)?;
+        let Some(crtc_h) = enc.crtc() else {
+            return Ok(chain);
+        };
+        let crtc_info = card.get_crtc(crtc_h)?;
+        chain.crtc_handle = Some(crtc_h);
+        chain.position = crtc_info.position();
+        chain.mode = crtc_info.mode();
+        chain.fb_handle = crtc_info.framebuffer();
+        Ok(chain)
     }
 
-    pub fn capture_monitor(&self, monitor_id: Option<u32>) -> Result<RgbaImage, McpError> {
+    /// Re-probe every card's outputs so hotplugs and mode changes are picked up.
+    /// Returns the output generation, which changes whenever the outputs do.
+    pub fn refresh(&self) -> u64 {
+        // Probe without holding the lock so captures are not held up meanwhile
+        let previous = self.outputs().active.clone();
+        let probed = self.probe_outputs(&previous);
+        let mut outputs = self.outputs();
+        if outputs.update(probed) {
+            let names: Vec<_> = outputs
+                .active
+                .iter()
+                .map(|o| format!("{}: {} {}x{}", o.id, o.connector_name, o.width, o.height))
+                .collect();
+            tracing::info!("KMS outputs changed: [{}]", names.join(", "));
+        }
+        outputs.generation
+    }
+
+    /// The last probed outputs. The lock is only held to read or swap them, never
+    /// across ioctls; a panic elsewhere cannot leave them half-updated, so a
+    /// poisoned lock is recovered rather than propagated.
+    fn outputs(&self) -> MutexGuard<'_, Outputs> {
+        self.outputs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// A copy of the output with `monitor_id` (else the primary one), so it can be
+    /// captured without holding the lock.
+    fn find_output(&self, monitor_id: Option<u32>) -> Result<ActiveOutput, McpError> {
+        let outputs = self.outputs();
         let output = match monitor_id {
-            Some(id) => self.outputs.get(id as usize).ok_or_else(|| {
-                McpError::invalid_params(format!("Monitor index {id} out of range"), None)
-            })?,
-            None => self.outputs.first().ok_or_else(|| {
-                McpError::internal_error("No active outputs", None)
+            Some(id) => outputs.active.iter().find(|o| o.id == id).ok_or_else(|| {
+                McpError::invalid_params(format!("Monitor {id} is not connected"), None)
             })?,
+            None => outputs
+                .active
+                .get(self.primary_index(&outputs.active))
+                .ok_or_else(|| McpError::internal_error("No active outputs", None))?,
         };
+        Ok(output.clone())
+    }
 
-        self.capture_fb(output)
+    /// Index of the primary output: the configured one if it is active, else the first.
+    fn primary_index(&self, outputs: &[ActiveOutput]) -> usize {
+        self.primary
+            .as_deref()
+            .and_then(|name| {
+                outputs
+                    .iter()
+                    .position(|o| o.matches_name(name, &self.cards[o.card]))
+            })
+            .unwrap_or(0)
+    }
+
+    /// Capture one output as of the last `refresh`. `Backend` resolves the monitor
+    /// through `monitor_layout` first, which re-probes, so this does not.
+    pub fn capture_monitor(&self, monitor_id: Option<u32>) -> Result<RgbaImage, McpError> {
+        let output = self.find_output(monitor_id)?;
+        self.cards[output.card].capture_fb(&output, output.full_area())
+    }
+
+    /// Capture `area` of a monitor, in monitor-relative pixels within its bounds.
+    /// Only the rows and columns inside `area` are read and converted. Like
+    /// `capture_monitor`, uses the outputs of the last `refresh`.
+    pub fn capture_region(&self, monitor_id: u32, area: Rect) -> Result<RgbaImage, McpError> {
+        let output = self.find_output(Some(monitor_id))?;
+        if output.full_area().intersect(&area) != Some(area) {
+            return Err(McpError::invalid_params(
+                "Region is outside screen bounds",
+                None,
+            ));
+        }
+
+        self.cards[output.card].capture_fb(&output, area)
+    }
+
+    /// Re-probe and report each output's ID, name, position and size only.
+    pub fn monitor_layout(&self) -> Result<Vec<MonitorInfo>, McpError> {
+        Ok(self.monitors(|_| MonitorInfo::default()))
     }
 
     pub fn list_monitors(&self) -> Result<Vec<MonitorInfo>, McpError> {
-        Ok(self
-            .outputs
+        Ok(self.monitors(|o| self.cards[o.card].monitor_details(o)))
+    }
+
+    /// Re-probe and describe every output, filling the fields other than its
+    /// geometry from `details`.
+    fn monitors(&self, details: impl Fn(&ActiveOutput) -> MonitorInfo) -> Vec<MonitorInfo> {
+        self.refresh();
+        // Details read connector and plane properties; do that outside the lock
+        let active = self.outputs().active.clone();
+        let primary = self.primary_index(&active);
+        active
             .iter()
             .enumerate()
             .map(|(i, o)| MonitorInfo {
-                id: i as u32,
+                id: o.id,
                 name: o.connector_name.clone(),
-                x: 0,
-                y: 0,
+                x: o.x,
+                y: o.y,
                 width: o.width,
                 height: o.height,
-                is_primary: i == 0,
+                is_primary: i == primary,
+                ..details(o)
             })
-            .collect())
+            .collect()
+    }
+}
+
+impl KmsCard {
+    /// Metadata that only `list_monitors` needs: refresh rate, rotation, physical
+    /// size and the EDID identification. Missing pieces are left unset.
+    fn monitor_details(&self, output: &ActiveOutput) -> MonitorInfo {
+        let connector = self.card.get_connector(output.connector_handle, false).ok();
+        let edid = self
+            .card
+            .properties(output.connector_handle)
+            .ok()
+            .and_then(|props| props.get("EDID").map(|(blob, _)| *blob))
+            .filter(|&blob| blob != 0)
+            .and_then(|blob| self.card.get_property_blob(blob).ok())
+            .and_then(|data| Edid::parse(&data));
+        let rotation = self
+            .planes_on_crtc(output.crtc_handle)
+            .ok()
+            .and_then(|planes| {
+                planes
+                    .iter()
+                    .find(|p| p.kind == PlaneKind::Primary)
+                    .and_then(|p| p.rotation)
+            });
+        // The connector reports the size in mm; the EDID only in whole centimetres
+        let size_mm = connector
+            .and_then(|c| c.size())
+            .filter(|&(w, h)| w > 0 && h > 0)
+            .or(edid.as_ref().and_then(|e| e.size_mm));
+
+        MonitorInfo {
+            refresh_rate: Some(refresh_rate(&output.mode)),
+            rotation,
+            scale_factor: Some(1.0),
+            width_mm: size_mm.map(|(w, _)| w),
+            height_mm: size_mm.map(|(_, h)| h),
+            make: edid.as_ref().map(|e| e.make.clone()),
+            model: edid.as_ref().map(|e| e.model.clone()),
+            serial: edid.and_then(|e| e.serial),
+            ..Default::default()
+        }
     }
 
-    fn capture_fb(&self, output: &ActiveOutput) -> Result<RgbaImage, McpError> {
+    /// Capture `area` (output-relative, within the output) of what `output` shows.
+    fn capture_fb(&self, output: &ActiveOutput, area: Rect) -> Result<RgbaImage, McpError> {
         // Refresh CRTC to get current framebuffer (may change due to page-flipping)
         let crtc_info = self.card.get_crtc(output.crtc_handle).map_err(|e| {
             McpError::internal_error(format!("Failed to get CRTC: {e}"), None)
         })?;
+
+        // The CRTC scans out its viewport at (crtc_x, crtc_y) in the framebuffer
+        let (x, y) = crtc_info.position();
+        let viewport = Rect {
+            x: x as i32,
+            y: y as i32,
+            width: output.width,
+            height: output.height,
+        };
+
+        // Overlays and the cursor are separate planes; composite them when present
+        match self.planes_on_crtc(output.crtc_handle) {
+            Ok(planes) => {
+                let placed: Vec<_> = planes
+                    .iter()
+                    .filter_map(|plane| {
+                        let placement = plane.placement(viewport);
+                        if placement.is_none() {
+                            tracing::debug!(
+                                "Skipping {} plane {}: no SRC_*/CRTC_* properties",
+                                plane.kind.name(),
+                                u32::from(plane.handle)
+                            );
+                        }
+                        placement.map(|(src, dst)| (plane, src, dst))
+                    })
+                    .collect();
+                // A lone plane is read directly unless it needs rotating or reflecting
+                if placed.len() > 1 || placed.iter().any(|(plane, _, _)| plane.is_transformed()) {
+                    return self.composite_planes(area, &placed);
+                }
+            }
+            Err(e) => tracing::debug!("Plane enumeration failed ({e}), using primary framebuffer"),
+        }
+
         let fb_handle = crtc_info.framebuffer().unwrap_or(output.fb_handle);
+        let fb_area = Rect {
+            x: area.x + x as i32,
+            y: area.y + y as i32,
+            ..area
+        };
+        self.read_fb(fb_handle, fb_area)
+    }
 
-        // Try GET_FB2 first for pixel format info, fall back to GET_FB
-        match self.capture_fb2(fb_handle, output.width, output.height) {
-            Ok(img) => Ok(img),
-            Err(fb2_err) => {
-                tracing::debug!("GET_FB2 failed ({fb2_err}), trying GET_FB");
-                self.capture_fb1(fb_handle, output.width, output.height)
+    /// Read the pixels of `area` (framebuffer coordinates) from a framebuffer.
+    fn read_fb(&self, fb_handle: framebuffer::Handle, area: Rect) -> Result<RgbaImage, McpError> {
+        // Try GET_FB2 first for pixel format info. Fall back to GET_FB only when the
+        // ioctl itself fails: GET_FB would hand back a buffer GET_FB2 rejected (tiled,
+        // compressed, unknown format) and it would be read as linear garbage.
+        match self.card.get_planar_framebuffer(fb_handle) {
+            Ok(info) => self.capture_fb2(&info, area),
+            Err(GetPlanarFramebufferError::Io(e)) => {
+                tracing::debug!("GET_FB2 failed ({e}), trying GET_FB");
+                self.capture_fb1(fb_handle, area)
             }
+            Err(e) => Err(McpError::internal_error(format!("GET_FB2 failed: {e}"), None)),
+        }
+    }
+
+    /// Planes currently scanning out on `crtc`, bottom to top.
+    fn planes_on_crtc(
+        &self,
+        crtc: crtc::Handle,
+    ) -> Result<Vec<PlaneState>, Box<dyn std::error::Error>> {
+        let mut planes = Vec::new();
+        for handle in self.card.plane_handles()? {
+            let info = self.card.get_plane(handle)?;
+            if info.crtc() != Some(crtc) {
+                continue;
+            }
+            let Some(fb_handle) = info.framebuffer() else {
+                continue;
+            };
+
+            let props = self.card.properties(handle)?;
+            let value = |name: &str| props.get(name).map(|(raw, _)| *raw);
+            let enum_name = |name: &str| props.get(name).and_then(|(_, e)| e.as_deref());
+
+            // SRC_* are 16.16 fixed point; CRTC_X/Y are signed
+            let src = match (
+                value("SRC_X"),
+                value("SRC_Y"),
+                value("SRC_W"),
+                value("SRC_H"),
+            ) {
+                (Some(x), Some(y), Some(w), Some(h)) if w > 0 && h > 0 => Some(Rect {
+                    x: (x >> 16) as i32,
+                    y: (y >> 16) as i32,
+                    width: (w >> 16) as u32,
+                    height: (h >> 16) as u32,
+                }),
+                _ => None,
+            };
+            let dst = match (
+                value("CRTC_X"),
+                value("CRTC_Y"),
+                value("CRTC_W"),
+                value("CRTC_H"),
+            ) {
+                (Some(x), Some(y), Some(w), Some(h)) if w > 0 && h > 0 => Some(Rect {
+                    x: x as i64 as i32,
+                    y: y as i64 as i32,
+                    width: w as u32,
+                    height: h as u32,
+                }),
+                _ => None,
+            };
+
+            planes.push(PlaneState {
+                handle,
+                kind: match enum_name("type") {
+                    Some("Primary") => PlaneKind::Primary,
+                    Some("Cursor") => PlaneKind::Cursor,
+                    _ => PlaneKind::Overlay,
+                },
+                fb_handle,
+                zpos: value("zpos"),
+                src,
+                dst,
+                alpha: value("alpha").map_or(0xFFFF, |a| a.min(0xFFFF) as u16),
+                // DRM_MODE_ROTATE_0/90/180/270 are bits 0-3, REFLECT_X/Y bits 4-5
+                rotation: value("rotation")
+                    .map(|bits| bits & 0xF)
+                    .filter(|bits| bits.is_power_of_two())
+                    .map(|bits| bits.trailing_zeros() * 90),
+                reflect_x: value("rotation").is_some_and(|bits| bits & 0x10 != 0),
+                reflect_y: value("rotation").is_some_and(|bits| bits & 0x20 != 0),
+                blend: match enum_name("pixel blend mode") {
+                    Some("None") => BlendMode::Opaque,
+                    Some("Coverage") => BlendMode::Coverage,
+                    _ => BlendMode::Premultiplied,
+                },
+            });
+        }
+
+        // zpos decides where the driver exposes it; otherwise primary < overlay < cursor
+        planes.sort_by_key(|p| (p.zpos, p.kind, u32::from(p.handle)));
+        Ok(planes)
+    }
+
+    /// Blend the part of `planes` inside `area` (CRTC coordinates), bottom to top,
+    /// each with its source and destination rectangle. Each plane reads only the
+    /// source pixels that land in `area`.
+    fn composite_planes(
+        &self,
+        area: Rect,
+        planes: &[(&PlaneState, Rect, Rect)],
+    ) -> Result<RgbaImage, McpError> {
+        let mut canvas = RgbaImage::from_pixel(area.width, area.height, Rgba([0, 0, 0, 0xFF]));
+
+        for &(plane, src, dst) in planes {
+            // Rotated and reflected planes do not map source columns onto destination
+            // columns, so they are read whole and clipped when blended
+            let window = if plane.is_transformed() {
+                dst.intersect(&area).map(|_| (src, dst))
+            } else {
+                plane_window(src, dst, area)
+            };
+            let Some((src, dst)) = window else {
+                continue;
+            };
+            let img = match self.plane_image(plane, src, dst) {
+                Ok(img) => img,
+                Err(e) if plane.kind == PlaneKind::Primary => return Err(e),
+                Err(e) => {
+                    tracing::warn!(
+                        "Skipping {} plane {}: {}",
+                        plane.kind.name(),
+                        u32::from(plane.handle),
+                        e.message
+                    );
+                    continue;
+                }
+            };
+            blend_plane(
+                &mut canvas,
+                &img,
+                dst.x - area.x,
+                dst.y - area.y,
+                plane.alpha,
+                plane.blend,
+            );
+        }
+        Ok(canvas)
+    }
+
+    /// The `src` rectangle of a plane's framebuffer, rotated and reflected as the
+    /// plane is and scaled to the `dst` size.
+    fn plane_image(&self, plane: &PlaneState, src: Rect, dst: Rect) -> Result<RgbaImage, McpError> {
+        let img = plane.orient(self.read_fb(plane.fb_handle, src)?);
+
+        if img.dimensions() == (dst.width, dst.height) {
+            Ok(img)
+        } else {
+            Ok(imageops::resize(
+                &img,
+                dst.width,
+                dst.height,
+                imageops::FilterType::Triangle,
+            ))
         }
     }
 
     fn capture_fb2(
         &self,
-        fb_handle: framebuffer::Handle,
-        width: u32,
-        height: u32,
+        info: &framebuffer::PlanarInfo,
+        area: Rect,
     ) -> Result<RgbaImage, McpError> {
-        let info = self.card.get_planar_framebuffer(fb_handle).map_err(|e| {
-            McpError::internal_error(format!("GET_FB2 failed: {e}"), None)
-        })?;
+        // Released however the capture ends, including the unsupported-format errors below
+        let gem_handles = GemHandles::new(&self.card, info.buffers().into_iter().flatten());
+        let format = info.pixel_format();
 
-        // Reject non-linear modifiers (tiled GPU buffers can't be mmap'd correctly)
-        if let Some(modifier) = info.modifier() {
-            if modifier != DrmModifier::Linear {
-                return Err(McpError::internal_error(
+        // Tiled buffers are mapped whole (padded to full tiles) and detiled on the CPU
+        let tiling = match info.modifier() {
+            None | Some(DrmModifier::Linear) => None,
+            Some(modifier) => Some(Tiling::from_modifier(modifier).ok_or_else(|| {
+                McpError::internal_error(
                     format!(
-                        "Framebuffer has non-linear modifier ({modifier:?}); \
-                         tiled buffers cannot be read via mmap"
+                        "Framebuffer has unsupported modifier ({modifier:?}); \
+                         only linear, I915 X/Y-tiled, VC4 T-tiled and Vivante tiled \
+                         buffers can be read"
                     ),
                     None,
+                )
+            })?),
+        };
+        let plane_count = pixel_format::plane_count(format);
+        let cpp = match tiling {
+            Some(_) if plane_count > 1 => {
+                return Err(McpError::internal_error(
+                    format!("Cannot detile multi-plane format {format:?}"),
+                    None,
                 ));
             }
-        }
+            Some(_) => pixel_format::bytes_per_pixel(format).ok_or_else(|| {
+                McpError::internal_error(
+                    format!("Cannot detile pixel format {format:?}"),
+                    None,
+                )
+            })?,
+            None => pixel_format::bytes_per_pixel(format).unwrap_or(0),
+        };
 
-        let gem_handle = info.buffers()[0].ok_or_else(|| {
-            McpError::internal_error("No buffer handle in framebuffer", None)
-        })?;
-        let pitch = info.pitches()[0];
-        let format = info.pixel_format();
+        // A linear single-plane buffer is read from the area's first row; tiled and
+        // multi-plane buffers from the top, then cropped after conversion.
+        let (x, y) = (area.x as u32, area.y as u32);
+        let (right, bottom) = (x + area.width, y + area.height);
+        let windowed = tiling.is_none() && plane_count == 1 && cpp > 0;
+
+        // Planes usually share one buffer object at different offsets (GET_FB2 then
+        // repeats the handle), so map each distinct GEM handle once, far enough to
+        // cover every plane that lives in it.
+        let (buffers, pitches, offsets) = (info.buffers(), info.pitches(), info.offsets());
+        let mut gems: Vec<(drm::buffer::Handle, Range<usize>)> = Vec::new();
+        let mut layout = Vec::with_capacity(plane_count);
+        for i in 0..plane_count {
+            let gem = buffers[i].or(buffers[0]).ok_or_else(|| {
+                McpError::internal_error("No buffer handle in framebuffer", None)
+            })?;
+            let (first_row, rows) = match (i, tiling) {
+                (0, Some(t)) => (0, t.padded_height(bottom, cpp)),
+                _ if windowed => (y, area.height),
+                _ => (0, pixel_format::plane_height(format, i, bottom)),
+            };
+            let pitch = pitches[i] as usize;
+            let start = offsets[i] as usize + pitch * first_row as usize;
+            let end = start + pitch * rows as usize;
+            let slot = match gems.iter().position(|(g, _)| *g == gem) {
+                Some(slot) => {
+                    let range = &mut gems[slot].1;
+                    *range = range.start.min(start)..range.end.max(end);
+                    slot
+                }
+                None => {
+                    gems.push((gem, start..end));
+                    gems.len() - 1
+                }
+            };
+            layout.push((slot, start, pitches[i]));
+        }
 
-        let raw = self.mmap_gem_buffer(gem_handle, height, pitch)?;
+        let mapped: Vec<_> = gems
+            .iter()
+            .map(|(gem, range)| self.mmap_gem_buffer(*gem, range.clone()))
+            .collect();
+        drop(gem_handles);
 
-        let rgba_data = pixel_format::convert_to_rgba(&raw, width, height, pitch, format)
-            .map_err(|e| McpError::internal_error(e, None))?;
+        let mapped = mapped.into_iter().collect::<Result<Vec<_>, _>>()?;
+        let planes: Vec<Plane> = layout
+            .iter()
+            .map(|&(slot, start, pitch)| Plane {
+                data: &mapped[slot][start - gems[slot].1.start..],
+                pitch,
+            })
+            .collect();
 
-        // close_buffer releases our reference to the GEM handle returned by GET_FB2
-        let _ = self.card.close_buffer(gem_handle);
+        let converted = match tiling {
+            Some(tiling) => {
+                let plane = &planes[0];
+                tiling::detile(plane.data, right, bottom, plane.pitch, cpp, tiling).and_then(
+                    |linear| {
+                        let rows = &linear[(y * plane.pitch) as usize..];
+                        convert_window(rows, area, plane.pitch, format)
+                    },
+                )
+            }
+            None if windowed => convert_window(planes[0].data, area, planes[0].pitch, format),
+            None => pixel_format::convert_planes_to_rgba(&planes, right, bottom, format)
+                .and_then(|rgba| {
+                    let image = RgbaImage::from_raw(right, bottom, rgba)
+                        .ok_or("Converted image has the wrong size")?;
+                    Ok(imageops::crop_imm(&image, x, y, area.width, area.height)
+                        .to_image()
+                        .into_raw())
+                }),
+        }
+        .map_err(|e| McpError::internal_error(e, None))?;
 
-        RgbaImage::from_raw(width, height, rgba_data).ok_or_else(|| {
+        RgbaImage::from_raw(area.width, area.height, converted).ok_or_else(|| {
             McpError::internal_error("Failed to create image from pixel data", None)
         })
     }
@@ -231,8 +993,7 @@ impl KmsBackend {
     fn capture_fb1(
         &self,
         fb_handle: framebuffer::Handle,
-        width: u32,
-        height: u32,
+        area: Rect,
     ) -> Result<RgbaImage, McpError> {
         let info = self.card.get_framebuffer(fb_handle).map_err(|e| {
             McpError::internal_error(format!("GET_FB failed: {e}"), None)
@@ -245,6 +1006,7 @@ impl KmsBackend {
                 None,
             )
         })?;
+        let gem_handles = GemHandles::new(&self.card, [gem_handle]);
 
         let pitch = info.pitch();
         let bpp = info.bpp();
@@ -256,7 +1018,6 @@ impl KmsBackend {
             (32, 32) => DrmFourcc::Argb8888,
             (16, 16) => DrmFourcc::Rgb565,
             _ => {
-                let _ = self.card.close_buffer(gem_handle);
                 return Err(McpError::internal_error(
                     format!("Unsupported framebuffer format: {bpp}bpp depth={depth}"),
                     None,
@@ -264,27 +1025,26 @@ impl KmsBackend {
             }
         };
 
-        let raw = self.mmap_gem_buffer(gem_handle, height, pitch)?;
+        // Only the area's rows are mapped
+        let start = area.y as usize * pitch as usize;
+        let raw = self.mmap_gem_buffer(gem_handle, start..start + area.height as usize * pitch as usize);
+        drop(gem_handles);
+        let raw = raw?;
 
-        let rgba_data = pixel_format::convert_to_rgba(&raw, width, height, pitch, format)
-            .map_err(|e| {
-                let _ = self.card.close_buffer(gem_handle);
-                McpError::internal_error(e, None)
-            })?;
-
-        let _ = self.card.close_buffer(gem_handle);
+        let rgba_data = convert_window(&raw, area, pitch, format)
+            .map_err(|e| McpError::internal_error(e, None))?;
 
-        RgbaImage::from_raw(width, height, rgba_data).ok_or_else(|| {
+        RgbaImage::from_raw(area.width, area.height, rgba_data).ok_or_else(|| {
             McpError::internal_error("Failed to create image from pixel data", None)
         })
     }
 
-    /// Export GEM handle as PRIME fd, mmap it, read pixels, munmap, close fd.
+    /// Export GEM handle as PRIME fd, mmap the pages covering `range`, copy those
+    /// bytes out, munmap, close fd.
     fn mmap_gem_buffer(
         &self,
         gem_handle: drm::buffer::Handle,
-        height: u32,
-        pitch: u32,
+        range: Range<usize>,
     ) -> Result<Vec<u8>, McpError> {
         let prime_fd: OwnedFd = self
             .card
@@ -293,9 +1053,11 @@ impl KmsBackend {
                 McpError::internal_error(format!("PRIME export failed: {e}"), None)
             })?;
 
-        let size = (height as usize) * (pitch as usize);
+        // mmap offsets must be page-aligned
+        let map_start = range.start - range.start % rustix::param::page_size();
+        let size = range.end - map_start;
 
-        // SAFETY: we own the prime_fd, and the mapping size matches the buffer.
+        // SAFETY: we own the prime_fd, and the mapping lies within the buffer.
         // We read the pixels into a Vec and immediately munmap.
         let data = unsafe {
             let ptr = mm::mmap(
@@ -304,14 +1066,14 @@ impl KmsBackend {
                 ProtFlags::READ,
                 MapFlags::SHARED,
                 &prime_fd,
-                0,
+                map_start as u64,
             )
             .map_err(|e| {
                 McpError::internal_error(format!("mmap failed: {e}"), None)
             })?;
 
             let slice = std::slice::from_raw_parts(ptr.cast::<u8>(), size);
-            let buf = slice.to_vec();
+            let buf = slice[range.start - map_start..].to_vec();
 
             let _ = mm::munmap(ptr, size);
             buf
@@ -320,3 +1082,619 @@ impl KmsBackend {
         Ok(data)
     }
 }
+
+/// Convert `area` of a single-plane linear buffer. `rows` starts at the first
+/// byte of the area's top row. YUYV shares chroma between pixel pairs, so an area
+/// starting on an odd column is converted from the start of the pair and trimmed.
+fn convert_window(rows: &[u8], area: Rect, pitch: u32, format: DrmFourcc) -> Result<Vec<u8>, String> {
+    let cpp = pixel_format::bytes_per_pixel(format)
+        .ok_or_else(|| format!("Unsupported pixel format: {format:?}"))?;
+    let lead = if format == DrmFourcc::Yuyv { area.x as u32 % 2 } else { 0 };
+    let first_byte = ((area.x as u32 - lead) * cpp) as usize;
+    let rgba = pixel_format::convert_to_rgba(
+        &rows[first_byte..],
+        area.width + lead,
+        area.height,
+        pitch,
+        format,
+    )?;
+    if lead == 0 {
+        return Ok(rgba);
+    }
+    let row_bytes = (area.width + lead) as usize * 4;
+    Ok(rgba
+        .chunks_exact(row_bytes)
+        .flat_map(|row| &row[lead as usize * 4..])
+        .copied()
+        .collect())
+}
+
+/// The part of a plane that lands in `area`: the source rectangle to read and the
+/// destination rectangle it scales to, or `None` when the plane misses `area`.
+/// Scaled planes round outwards, so the destination may overhang `area` slightly.
+fn plane_window(src: Rect, dst: Rect, area: Rect) -> Option<(Rect, Rect)> {
+    let visible = dst.intersect(&area)?;
+    let (dx, dy) = ((visible.x - dst.x) as u32, (visible.y - dst.y) as u32);
+    if (src.width, src.height) == (dst.width, dst.height) {
+        let src_part = Rect {
+            x: src.x + dx as i32,
+            y: src.y + dy as i32,
+            ..visible
+        };
+        return Some((src_part, visible));
+    }
+
+    let (sx, sw) = scale_span(dx, visible.width, dst.width, src.width);
+    let (sy, sh) = scale_span(dy, visible.height, dst.height, src.height);
+    // Where that source span lands once scaled
+    let (px, pw) = scale_span(sx, sw, src.width, dst.width);
+    let (py, ph) = scale_span(sy, sh, src.height, dst.height);
+    Some((
+        Rect {
+            x: src.x + sx as i32,
+            y: src.y + sy as i32,
+            width: sw,
+            height: sh,
+        },
+        Rect {
+            x: dst.x + px as i32,
+            y: dst.y + py as i32,
+            width: pw,
+            height: ph,
+        },
+    ))
+}
+
+/// Map `len` units from `start` on an axis `from` long onto one `to` long,
+/// rounding outwards. Returns the new start and length.
+fn scale_span(start: u32, len: u32, from: u32, to: u32) -> (u32, u32) {
+    let (from, to) = (from as u64, to as u64);
+    let begin = start as u64 * to / from;
+    let end = ((start + len) as u64 * to).div_ceil(from).min(to);
+    (begin as u32, (end - begin) as u32)
+}
+
+/// Blend `img` onto the opaque `canvas` with its top-left corner at (`x`, `y`),
+/// following the KMS plane blending equations for `blend`.
+fn blend_plane(
+    canvas: &mut RgbaImage,
+    img: &RgbaImage,
+    x: i32,
+    y: i32,
+    alpha: u16,
+    blend: BlendMode,
+) {
+    let plane_alpha = alpha as f32 / 65535.0;
+    for (ix, iy, fg) in img.enumerate_pixels() {
+        let (cx, cy) = (x as i64 + ix as i64, y as i64 + iy as i64);
+        if cx < 0 || cy < 0 || cx >= canvas.width() as i64 || cy >= canvas.height() as i64 {
+            continue;
+        }
+        let bg = canvas.get_pixel_mut(cx as u32, cy as u32);
+        let pixel_alpha = fg[3] as f32 / 255.0;
+        // out = fg_weight * fg + (1 - bg_coverage) * bg
+        let (fg_weight, coverage) = match blend {
+            BlendMode::Opaque => (plane_alpha, plane_alpha),
+            BlendMode::Premultiplied => (plane_alpha, plane_alpha * pixel_alpha),
+            BlendMode::Coverage => (plane_alpha * pixel_alpha, plane_alpha * pixel_alpha),
+        };
+        for c in 0..3 {
+            let out = fg_weight * fg[c] as f32 + (1.0 - coverage) * bg[c] as f32;
+            bg[c] = out.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+// -- Diagnostics --
+
+/// Outcome of one diagnostic step.
+#[derive(Serialize)]
+pub struct ProbeCheck {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl<T, E: std::fmt::Display> From<&Result<T, E>> for ProbeCheck {
+    fn from(result: &Result<T, E>) -> Self {
+        ProbeCheck {
+            ok: result.is_ok(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        }
+    }
+}
+
+/// Everything `KmsBackend::open` looks at, step by step.
+#[derive(Serialize)]
+pub struct KmsDiagnostics {
+    /// Why `/dev/dri` could not be listed.
+    pub dri_error: Option<String>,
+    pub cards: Vec<CardDiagnostics>,
+}
+
+#[derive(Serialize)]
+pub struct CardDiagnostics {
+    pub path: String,
+    pub driver: Option<String>,
+    /// Why the card could not be opened or probed.
+    pub error: Option<String>,
+    pub connectors: Vec<ConnectorDiagnostics>,
+}
+
+#[derive(Serialize)]
+pub struct ConnectorDiagnostics {
+    /// ID the connector is listed under while active.
+    pub monitor_id: Option<u32>,
+    pub name: String,
+    pub state: &'static str,
+    pub crtc: Option<u32>,
+    /// Current mode as `WIDTHxHEIGHT@HZ`.
+    pub mode: Option<String>,
+    /// Whether the backend would capture this connector as a monitor.
+    pub active: bool,
+    pub framebuffer: Option<FramebufferDiagnostics>,
+    /// Planes scanning out on the CRTC, bottom to top, as composited into captures.
+    pub planes: Vec<PlaneDiagnostics>,
+}
+
+#[derive(Serialize)]
+pub struct PlaneDiagnostics {
+    pub id: u32,
+    pub kind: &'static str,
+    pub framebuffer: u32,
+    pub zpos: Option<u64>,
+    /// Destination rectangle on the CRTC; `None` when the plane covers all of it.
+    pub dst: Option<Rect>,
+}
+
+#[derive(Serialize)]
+pub struct FramebufferDiagnostics {
+    pub id: u32,
+    pub get_fb2: ProbeCheck,
+    pub pixel_format: Option<String>,
+    pub modifier: Option<String>,
+    /// Whether the pixel format can be converted to RGBA.
+    pub format_supported: Option<bool>,
+    pub get_fb: ProbeCheck,
+    /// Whether GET_FB/GET_FB2 returned a GEM handle; the kernel withholds it
+    /// without CAP_SYS_ADMIN.
+    pub buffer_handle: bool,
+    /// A full capture through the normal GET_FB2/GET_FB path.
+    pub capture: ProbeCheck,
+}
+
+impl From<&PlaneState> for PlaneDiagnostics {
+    fn from(plane: &PlaneState) -> Self {
+        PlaneDiagnostics {
+            id: plane.handle.into(),
+            kind: plane.kind.name(),
+            framebuffer: plane.fb_handle.into(),
+            zpos: plane.zpos,
+            dst: plane.dst,
+        }
+    }
+}
+
+impl KmsBackend {
+    /// Probe every DRI card the way `open` does, recording each step instead of
+    /// skipping cards that fail.
+    pub fn diagnose() -> KmsDiagnostics {
+        match Self::card_paths() {
+            Ok(paths) => KmsDiagnostics {
+                dri_error: None,
+                cards: paths.iter().map(|p| Self::diagnose_card(&p.to_string_lossy())).collect(),
+            },
+            Err(e) => KmsDiagnostics {
+                dri_error: Some(format!("Cannot list /dev/dri: {e}")),
+                cards: Vec::new(),
+            },
+        }
+    }
+
+    fn diagnose_card(path: &str) -> CardDiagnostics {
+        let mut report = CardDiagnostics {
+            path: path.to_string(),
+            driver: None,
+            error: None,
+            connectors: Vec::new(),
+        };
+        let card = match Card::open(path) {
+            Ok(c) => c,
+            Err(e) => {
+                report.error = Some(format!("Cannot open: {e}"));
+                return report;
+            }
+        };
+        report.driver = card
+            .get_driver()
+            .ok()
+            .map(|d| d.name().to_string_lossy().into_owned());
+
+        let res = match card.resource_handles() {
+            Ok(res) => res,
+            Err(e) => {
+                report.error = Some(format!("Cannot read mode resources: {e}"));
+                return report;
+            }
+        };
+
+        let card = KmsCard {
+            card,
+            path: path.to_string(),
+            number: path
+                .strip_prefix("/dev/dri/card")
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(0),
+        };
+        for &conn_h in res.connectors() {
+            let chain = match Self::connector_chain(&card.card, conn_h) {
+                Ok(chain) => chain,
+                Err(e) => {
+                    report.error = Some(format!("Probe failed: {e}"));
+                    return report;
+                }
+            };
+            let output = chain.active_output(0, card.number);
+            report.connectors.push(ConnectorDiagnostics {
+                monitor_id: output.as_ref().map(|o| o.id),
+                name: chain.name,
+                state: match chain.state {
+                    connector::State::Connected => "connected",
+                    connector::State::Disconnected => "disconnected",
+                    connector::State::Unknown => "unknown",
+                },
+                crtc: chain.crtc_handle.map(u32::from),
+                mode: chain.mode.map(|m| {
+                    let (w, h) = m.size();
+                    format!("{w}x{h}@{}", m.vrefresh())
+                }),
+                active: output.is_some(),
+                framebuffer: output.as_ref().map(|o| card.diagnose_framebuffer(o)),
+                planes: output
+                    .and_then(|o| card.planes_on_crtc(o.crtc_handle).ok())
+                    .unwrap_or_default()
+                    .iter()
+                    .map(PlaneDiagnostics::from)
+                    .collect(),
+            });
+        }
+        report
+    }
+}
+
+impl KmsCard {
+    fn diagnose_framebuffer(&self, output: &ActiveOutput) -> FramebufferDiagnostics {
+        let mut buffer_handle = false;
+
+        let fb2 = self.card.get_planar_framebuffer(output.fb_handle);
+        let (pixel_format, modifier) = match &fb2 {
+            Ok(info) => {
+                let gems = GemHandles::new(&self.card, info.buffers().into_iter().flatten());
+                buffer_handle = !gems.handles.is_empty();
+                (Some(info.pixel_format()), info.modifier())
+            }
+            Err(_) => (None, None),
+        };
+
+        let fb1 = self.card.get_framebuffer(output.fb_handle);
+        if let Ok(info) = &fb1
+            && let Some(gem) = info.buffer()
+        {
+            buffer_handle = true;
+            drop(GemHandles::new(&self.card, [gem]));
+        }
+
+        FramebufferDiagnostics {
+            id: output.fb_handle.into(),
+            get_fb2: ProbeCheck::from(&fb2),
+            pixel_format: pixel_format.map(|f| format!("{f:?}")),
+            modifier: modifier.map(|m| format!("{m:?}")),
+            format_supported: pixel_format.map(pixel_format::is_supported),
+            get_fb: ProbeCheck::from(&fb1),
+            buffer_handle,
+            capture: ProbeCheck::from(
+                &self
+                    .capture_fb(output, output.full_area())
+                    .map_err(|e| e.message),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn plane(kind: PlaneKind, src: Option<Rect>, dst: Option<Rect>) -> PlaneState {
+        PlaneState {
+            handle: drm::control::from_u32(31).unwrap(),
+            kind,
+            fb_handle: drm::control::from_u32(42).unwrap(),
+            zpos: None,
+            src,
+            dst,
+            alpha: 0xFFFF,
+            blend: BlendMode::Premultiplied,
+            rotation: None,
+            reflect_x: false,
+            reflect_y: false,
+        }
+    }
+
+    #[test]
+    fn primary_without_properties_scans_out_the_viewport() {
+        let viewport = rect(1920, 0, 1280, 1024);
+        let primary = plane(PlaneKind::Primary, None, None);
+        assert_eq!(
+            primary.placement(viewport),
+            Some((viewport, rect(0, 0, 1280, 1024)))
+        );
+    }
+
+    #[test]
+    fn planes_without_properties_are_not_placed() {
+        let viewport = rect(0, 0, 1920, 1080);
+        assert_eq!(
+            plane(PlaneKind::Cursor, None, None).placement(viewport),
+            None
+        );
+        assert_eq!(
+            plane(PlaneKind::Overlay, Some(rect(0, 0, 64, 64)), None).placement(viewport),
+            None
+        );
+    }
+
+    #[test]
+    fn planes_with_properties_use_them() {
+        let cursor = plane(
+            PlaneKind::Cursor,
+            Some(rect(0, 0, 64, 64)),
+            Some(rect(100, 200, 64, 64)),
+        );
+        assert_eq!(
+            cursor.placement(rect(0, 0, 1920, 1080)),
+            Some((rect(0, 0, 64, 64), rect(100, 200, 64, 64)))
+        );
+    }
+
+    #[test]
+    fn plane_window_reads_only_the_visible_part() {
+        // A 64x64 cursor at (100, 200), half inside the area
+        let (src, dst) = (rect(0, 0, 64, 64), rect(100, 200, 64, 64));
+        assert_eq!(
+            plane_window(src, dst, rect(132, 0, 500, 500)),
+            Some((rect(32, 0, 32, 64), rect(132, 200, 32, 64)))
+        );
+        assert_eq!(plane_window(src, dst, rect(0, 0, 100, 100)), None);
+    }
+
+    #[test]
+    fn plane_window_scales_outwards() {
+        // Upscaled 2x: 51 visible pixels need 25.5 source pixels, so 26 are read
+        let (src, dst) = (rect(0, 0, 100, 100), rect(10, 10, 200, 200));
+        assert_eq!(
+            plane_window(src, dst, rect(0, 0, 61, 61)),
+            Some((rect(0, 0, 26, 26), rect(10, 10, 52, 52)))
+        );
+        // Downscaled 2x from a panned source
+        let (src, dst) = (rect(5, 5, 200, 200), rect(0, 0, 100, 100));
+        assert_eq!(
+            plane_window(src, dst, rect(25, 25, 10, 10)),
+            Some((rect(55, 55, 20, 20), rect(25, 25, 10, 10)))
+        );
+    }
+
+    #[test]
+    fn convert_window_trims_the_yuyv_lead_pixel() {
+        // Black, white, red, red in two YUYV pairs; the window starts mid-pair
+        let row = [16, 128, 235, 128, 81, 90, 81, 240];
+        let window = convert_window(&row, rect(1, 0, 2, 1), 8, DrmFourcc::Yuyv).unwrap();
+        assert_eq!(window, [255, 255, 255, 0xFF, 255, 0, 0, 0xFF]);
+
+        let full = pixel_format::convert_to_rgba(&row, 4, 1, 8, DrmFourcc::Yuyv).unwrap();
+        let window = convert_window(&row, rect(3, 0, 1, 1), 8, DrmFourcc::Yuyv).unwrap();
+        assert_eq!(window, full[12..16]);
+    }
+
+    #[test]
+    fn blend_plane_clips_to_the_canvas() {
+        let mut canvas = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 0xFF]));
+        let cursor = RgbaImage::from_pixel(2, 2, Rgba([255, 255, 255, 255]));
+        blend_plane(
+            &mut canvas,
+            &cursor,
+            3,
+            -1,
+            0xFFFF,
+            BlendMode::Premultiplied,
+        );
+        assert_eq!(canvas.get_pixel(3, 0), &Rgba([255, 255, 255, 0xFF]));
+        assert_eq!(canvas.get_pixel(2, 0), &Rgba([0, 0, 0, 0xFF]));
+        assert_eq!(canvas.get_pixel(3, 1), &Rgba([0, 0, 0, 0xFF]));
+    }
+
+    #[test]
+    fn blend_plane_follows_the_blend_mode() {
+        let bg = Rgba([100, 100, 100, 0xFF]);
+        // Half-transparent pixel whose colour is already premultiplied
+        let fg = RgbaImage::from_pixel(1, 1, Rgba([100, 0, 0, 128]));
+        let blend = |alpha, mode| {
+            let mut canvas = RgbaImage::from_pixel(1, 1, bg);
+            blend_plane(&mut canvas, &fg, 0, 0, alpha, mode);
+            *canvas.get_pixel(0, 0)
+        };
+
+        assert_eq!(blend(0xFFFF, BlendMode::Opaque), Rgba([100, 0, 0, 0xFF]));
+        assert_eq!(
+            blend(0xFFFF, BlendMode::Premultiplied),
+            Rgba([150, 50, 50, 0xFF])
+        );
+        assert_eq!(
+            blend(0xFFFF, BlendMode::Coverage),
+            Rgba([100, 50, 50, 0xFF])
+        );
+        assert_eq!(blend(0, BlendMode::Opaque), bg);
+    }
+
+    /// A 2x1 cursor, red on the left and blue on the right.
+    fn two_pixels() -> RgbaImage {
+        let mut img = RgbaImage::from_pixel(2, 1, Rgba([255, 0, 0, 255]));
+        img.put_pixel(1, 0, Rgba([0, 0, 255, 255]));
+        img
+    }
+
+    fn colors(img: &RgbaImage) -> Vec<[u8; 4]> {
+        img.pixels().map(|p| p.0).collect()
+    }
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    #[test]
+    fn orient_rotates_counter_clockwise() {
+        let mut cursor = plane(PlaneKind::Cursor, None, None);
+        assert!(!cursor.is_transformed());
+        cursor.rotation = Some(0);
+        assert!(!cursor.is_transformed());
+
+        // Rotated 90 degrees counter-clockwise the right end ends up on top
+        cursor.rotation = Some(90);
+        assert!(cursor.is_transformed());
+        let img = cursor.orient(two_pixels());
+        assert_eq!(img.dimensions(), (1, 2));
+        assert_eq!(colors(&img), [BLUE, RED]);
+
+        cursor.rotation = Some(180);
+        assert_eq!(colors(&cursor.orient(two_pixels())), [BLUE, RED]);
+        cursor.rotation = Some(270);
+        assert_eq!(colors(&cursor.orient(two_pixels())), [RED, BLUE]);
+    }
+
+    #[test]
+    fn orient_reflects_before_rotating() {
+        let mut cursor = plane(PlaneKind::Cursor, None, None);
+        cursor.reflect_x = true;
+        assert!(cursor.is_transformed());
+        assert_eq!(colors(&cursor.orient(two_pixels())), [BLUE, RED]);
+
+        // Mirrored to blue-red first, so red is on the right and rotates to the top
+        cursor.rotation = Some(90);
+        assert_eq!(colors(&cursor.orient(two_pixels())), [RED, BLUE]);
+    }
+
+    #[test]
+    fn rotated_planes_blend_in_their_destination_orientation() {
+        // A 2x1 source rotated to a 1x2 destination at (1, 0) is not squashed
+        let mut cursor = plane(
+            PlaneKind::Cursor,
+            Some(rect(0, 0, 2, 1)),
+            Some(rect(1, 0, 1, 2)),
+        );
+        cursor.rotation = Some(90);
+        let img = cursor.orient(two_pixels());
+        assert_eq!(img.dimensions(), (1, 2));
+
+        let mut canvas = RgbaImage::from_pixel(3, 2, Rgba([0, 0, 0, 0xFF]));
+        blend_plane(&mut canvas, &img, 1, 0, 0xFFFF, BlendMode::Premultiplied);
+        assert_eq!(canvas.get_pixel(1, 0).0, BLUE);
+        assert_eq!(canvas.get_pixel(1, 1).0, RED);
+        assert_eq!(canvas.get_pixel(0, 0).0, [0, 0, 0, 0xFF]);
+    }
+
+    fn blank_mode() -> Mode {
+        // SAFETY: `Mode` wraps drm_mode_modeinfo, plain integers that are valid when zeroed
+        unsafe { std::mem::zeroed() }
+    }
+
+    fn chain(interface: connector::Interface, interface_id: u32) -> ConnectorChain {
+        ConnectorChain {
+            handle: drm::control::from_u32(77).unwrap(),
+            name: format!("{}-{interface_id}", interface.as_str()),
+            interface,
+            interface_id,
+            state: connector::State::Connected,
+            crtc_handle: drm::control::from_u32(51),
+            position: (0, 0),
+            mode: Some(blank_mode()),
+            fb_handle: drm::control::from_u32(42),
+        }
+    }
+
+    fn output(id: u32, card: usize, fb: u32, crtc_x: u32, width: u32) -> ActiveOutput {
+        ActiveOutput {
+            id,
+            card,
+            connector_handle: drm::control::from_u32(77).unwrap(),
+            connector_name: String::new(),
+            crtc_handle: drm::control::from_u32(51).unwrap(),
+            crtc_x,
+            crtc_y: 0,
+            x: 0,
+            y: 0,
+            width,
+            height: 1080,
+            mode: blank_mode(),
+            fb_handle: drm::control::from_u32(fb).unwrap(),
+        }
+    }
+
+    #[test]
+    fn monitor_ids_encode_card_and_connector() {
+        use connector::Interface;
+        assert_eq!(monitor_id(0, Interface::HDMIA, 1), 1101);
+        assert_eq!(monitor_id(1, Interface::DisplayPort, 2), 11002);
+        assert_eq!(monitor_id(0, Interface::EmbeddedDisplayPort, 1), 1401);
+        assert_ne!(
+            monitor_id(0, Interface::HDMIA, 1),
+            monitor_id(0, Interface::HDMIA, 2)
+        );
+    }
+
+    #[test]
+    fn monitor_ids_do_not_depend_on_probe_order() {
+        // The same connector keeps its ID whichever position its card was opened at
+        let hdmi = chain(connector::Interface::HDMIA, 1);
+        let first = hdmi.active_output(0, 1).unwrap();
+        let later = hdmi.active_output(3, 1).unwrap();
+        assert_eq!(first.id, 11101);
+        assert_eq!(first.id, later.id);
+        assert_ne!(hdmi.active_output(0, 0).unwrap().id, first.id);
+    }
+
+    #[test]
+    fn lay_out_keeps_crtc_offsets_within_a_shared_framebuffer() {
+        let mut outputs = [
+            output(1101, 0, 42, 0, 1920),
+            output(1102, 0, 42, 1920, 1280),
+        ];
+        lay_out(&mut outputs);
+        assert_eq!((outputs[0].x, outputs[1].x), (0, 1920));
+    }
+
+    #[test]
+    fn lay_out_places_separate_framebuffers_side_by_side() {
+        let mut outputs = [
+            output(1101, 0, 42, 0, 1920),
+            output(11001, 1, 43, 0, 1280),
+            output(11002, 1, 44, 0, 800),
+        ];
+        lay_out(&mut outputs);
+        let placed: Vec<_> = outputs.iter().map(|o| (o.id, o.x, o.y)).collect();
+        assert_eq!(placed, [(1101, 0, 0), (11001, 1920, 0), (11002, 3200, 0)]);
+
+        // Unplugging the first output moves the others but keeps their IDs
+        let mut remaining = [output(11001, 1, 43, 0, 1280), output(11002, 1, 44, 0, 800)];
+        lay_out(&mut remaining);
+        let placed: Vec<_> = remaining.iter().map(|o| (o.id, o.x)).collect();
+        assert_eq!(placed, [(11001, 0), (11002, 1280)]);
+    }
+}
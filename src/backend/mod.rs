@@ -2,37 +2,94 @@
 mod xcap;
 #[cfg(feature = "kms")]
 mod kms;
-#[cfg(feature = "kms")]
+#[cfg(feature = "fbdev")]
+mod fbdev;
+#[cfg(any(feature = "kms", feature = "fbdev"))]
 mod pixel_format;
+#[cfg(feature = "kms")]
+mod tiling;
+#[cfg(feature = "kms")]
+mod edid;
+mod cache;
 
 #[cfg(feature = "desktop")]
 pub use self::xcap::XcapBackend;
 #[cfg(feature = "kms")]
-pub use self::kms::KmsBackend;
+pub use self::kms::{KmsBackend, KmsDiagnostics, ProbeCheck};
+#[cfg(feature = "fbdev")]
+pub use self::fbdev::{FbdevBackend, FbdevDiagnostics};
 
-use image::{DynamicImage, RgbaImage};
+use std::sync::Arc;
+use std::time::Duration;
+
+use image::{DynamicImage, RgbaImage, imageops};
 use rmcp::ErrorData as McpError;
+use schemars::JsonSchema;
 use serde::Serialize;
 
+use self::cache::FrameCache;
+
 // -- Shared data types --
 
-#[derive(Serialize)]
+#[derive(Default, Serialize, JsonSchema)]
 pub struct MonitorInfo {
+    /// ID accepted by the `monitor_id` tool parameters
     pub id: u32,
     pub name: String,
+    /// Left edge in global desktop coordinates
     pub x: i32,
+    /// Top edge in global desktop coordinates
     pub y: i32,
     pub width: u32,
     pub height: u32,
     pub is_primary: bool,
+    /// Refresh rate in Hz
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_rate: Option<f32>,
+    /// Rotation in degrees (0, 90, 180 or 270), counter-clockwise as KMS and RandR report it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<u32>,
+    /// Physical pixels per logical pixel
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale_factor: Option<f32>,
+    /// Physical width of the screen in millimetres
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width_mm: Option<u32>,
+    /// Physical height of the screen in millimetres
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height_mm: Option<u32>,
+    /// Manufacturer ID from the EDID, e.g. `DEL`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub make: Option<String>,
+    /// Model name from the EDID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Serial number from the EDID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub serial: Option<String>,
+}
+
+impl MonitorInfo {
+    /// Position and size in global desktop coordinates.
+    pub fn rect(&self) -> Rect {
+        Rect {
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+        }
+    }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 pub struct WindowInfo {
+    /// ID accepted by the `window_id` tool parameter
     pub id: u32,
     pub title: String,
     pub app_name: String,
+    /// Left edge in global desktop coordinates
     pub x: i32,
+    /// Top edge in global desktop coordinates
     pub y: i32,
     pub width: u32,
     pub height: u32,
@@ -40,53 +97,328 @@ pub struct WindowInfo {
     pub is_maximized: bool,
 }
 
+/// Criteria for picking a single window. Every criterion that is set must match,
+/// and exactly one window may match.
+#[derive(Debug, Default, Clone)]
+pub struct WindowSelector {
+    pub id: Option<u32>,
+    /// Exact window title.
+    pub title: Option<String>,
+    /// Regular expression searched for in the window title.
+    pub title_regex: Option<String>,
+    /// Application name, compared case-insensitively.
+    pub app_name: Option<String>,
+    pub pid: Option<u32>,
+    /// Only the window that currently has keyboard focus.
+    pub focused: bool,
+}
+
+impl WindowSelector {
+    pub fn by_id(id: u32) -> Self {
+        Self {
+            id: Some(id),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.id.is_none()
+            && self.title.is_none()
+            && self.title_regex.is_none()
+            && self.app_name.is_none()
+            && self.pid.is_none()
+            && !self.focused
+    }
+}
+
+impl std::fmt::Display for WindowSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(id) = self.id {
+            parts.push(format!("id={id}"));
+        }
+        if let Some(title) = &self.title {
+            parts.push(format!("title={title:?}"));
+        }
+        if let Some(re) = &self.title_regex {
+            parts.push(format!("title_regex={re:?}"));
+        }
+        if let Some(app) = &self.app_name {
+            parts.push(format!("app_name={app:?}"));
+        }
+        if let Some(pid) = self.pid {
+            parts.push(format!("pid={pid}"));
+        }
+        if self.focused {
+            parts.push("focused".to_string());
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Axis-aligned rectangle in pixel coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    fn right(&self) -> i64 {
+        self.x as i64 + self.width as i64
+    }
+
+    fn bottom(&self) -> i64 {
+        self.y as i64 + self.height as i64
+    }
+
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        if right <= x as i64 || bottom <= y as i64 {
+            return None;
+        }
+        Some(Rect {
+            x,
+            y,
+            width: (right - x as i64) as u32,
+            height: (bottom - y as i64) as u32,
+        })
+    }
+
+    /// `self` with every edge multiplied by `scale`, rounded outwards to whole pixels.
+    pub fn scaled(&self, scale: f64) -> Rect {
+        if scale == 1.0 {
+            return *self;
+        }
+        let x = (self.x as f64 * scale).floor();
+        let y = (self.y as f64 * scale).floor();
+        let right = (self.right() as f64 * scale).ceil();
+        let bottom = (self.bottom() as f64 * scale).ceil();
+        Rect {
+            x: x as i32,
+            y: y as i32,
+            width: (right - x) as u32,
+            height: (bottom - y) as u32,
+        }
+    }
+
+    /// The parts of `self` not covered by `other`, as up to four disjoint rectangles.
+    pub fn subtract(&self, other: &Rect) -> Vec<Rect> {
+        let Some(inner) = self.intersect(other) else {
+            return vec![*self];
+        };
+        let mut parts = Vec::new();
+        if inner.y > self.y {
+            parts.push(Rect {
+                x: self.x,
+                y: self.y,
+                width: self.width,
+                height: (inner.y - self.y) as u32,
+            });
+        }
+        if inner.bottom() < self.bottom() {
+            parts.push(Rect {
+                x: self.x,
+                y: inner.bottom() as i32,
+                width: self.width,
+                height: (self.bottom() - inner.bottom()) as u32,
+            });
+        }
+        if inner.x > self.x {
+            parts.push(Rect {
+                x: self.x,
+                y: inner.y,
+                width: (inner.x - self.x) as u32,
+                height: inner.height,
+            });
+        }
+        if inner.right() < self.right() {
+            parts.push(Rect {
+                x: inner.right() as i32,
+                y: inner.y,
+                width: (self.right() - inner.right()) as u32,
+                height: inner.height,
+            });
+        }
+        parts
+    }
+}
+
+/// Result of a region capture.
+pub struct RegionCapture {
+    /// The on-screen part of the requested region.
+    pub image: DynamicImage,
+    /// Global desktop position of the image's top-left pixel.
+    pub origin: (i32, i32),
+    /// Image pixels per desktop unit; above 1 on scaled displays.
+    pub scale: f64,
+    /// Parts of the requested region (global coordinates) that no monitor covers.
+    pub off_screen: Vec<Rect>,
+}
+
 // -- Backend capabilities --
 
 pub struct BackendCapabilities {
     pub supports_windows: bool,
+    /// Monitors can appear, disappear or change mode while running, and
+    /// `refresh_monitors` reports it.
+    pub supports_hotplug: bool,
+    /// Part of a monitor can be captured without capturing all of it.
+    pub native_region: bool,
 }
 
 // -- Backend enum --
 
-pub enum Backend {
+enum BackendImpl {
     #[cfg(feature = "desktop")]
     Xcap(XcapBackend),
     #[cfg(feature = "kms")]
     Kms(KmsBackend),
+    #[cfg(feature = "fbdev")]
+    Fbdev(FbdevBackend),
+}
+
+pub struct Backend {
+    inner: BackendImpl,
+    /// Recent monitor captures, when enabled with `with_frame_cache`.
+    frame_cache: Option<FrameCache>,
+}
+
+impl From<BackendImpl> for Backend {
+    fn from(inner: BackendImpl) -> Self {
+        Self {
+            inner,
+            frame_cache: None,
+        }
+    }
 }
 
 impl Backend {
+    /// Reuse monitor captures for up to `ttl`; a zero TTL leaves caching off.
+    pub fn with_frame_cache(mut self, ttl: Duration) -> Self {
+        self.frame_cache = (!ttl.is_zero()).then(|| FrameCache::new(ttl));
+        self
+    }
+
     pub fn capabilities(&self) -> BackendCapabilities {
-        match self {
+        match &self.inner {
             #[cfg(feature = "desktop")]
-            Backend::Xcap(_) => BackendCapabilities {
+            BackendImpl::Xcap(_) => BackendCapabilities {
                 supports_windows: true,
+                supports_hotplug: false,
+                native_region: false,
             },
             #[cfg(feature = "kms")]
-            Backend::Kms(_) => BackendCapabilities {
+            BackendImpl::Kms(_) => BackendCapabilities {
+                supports_windows: false,
+                supports_hotplug: true,
+                native_region: true,
+            },
+            #[cfg(feature = "fbdev")]
+            BackendImpl::Fbdev(_) => BackendCapabilities {
                 supports_windows: false,
+                supports_hotplug: false,
+                native_region: false,
             },
         }
     }
 
     pub fn name(&self) -> &'static str {
-        match self {
+        match &self.inner {
+            #[cfg(feature = "desktop")]
+            BackendImpl::Xcap(_) => "xcap",
+            #[cfg(feature = "kms")]
+            BackendImpl::Kms(_) => "kms",
+            #[cfg(feature = "fbdev")]
+            BackendImpl::Fbdev(_) => "fbdev",
+        }
+    }
+
+    /// Capture one monitor (explicit ID, else primary). With the frame cache enabled
+    /// a recent capture is reused unless `fresh` is set.
+    pub fn capture_monitor(
+        &self,
+        monitor_id: Option<u32>,
+        fresh: bool,
+    ) -> Result<RgbaImage, McpError> {
+        match &self.frame_cache {
+            Some(cache) => self
+                .cached_frame(cache, monitor_id, fresh)
+                .map(Arc::unwrap_or_clone),
+            None => self.capture_monitor_uncached(monitor_id),
+        }
+    }
+
+    fn cached_frame(
+        &self,
+        cache: &FrameCache,
+        monitor_id: Option<u32>,
+        fresh: bool,
+    ) -> Result<Arc<RgbaImage>, McpError> {
+        let id = match monitor_id {
+            Some(id) => id,
+            None => self.monitor_info(None)?.id,
+        };
+        cache.get_or_capture(id, fresh, || self.capture_monitor_uncached(Some(id)))
+    }
+
+    fn capture_monitor_uncached(&self, monitor_id: Option<u32>) -> Result<RgbaImage, McpError> {
+        match &self.inner {
             #[cfg(feature = "desktop")]
-            Backend::Xcap(_) => "xcap",
+            BackendImpl::Xcap(b) => b.capture_monitor(monitor_id),
             #[cfg(feature = "kms")]
-            Backend::Kms(_) => "kms",
+            BackendImpl::Kms(b) => b.capture_monitor(monitor_id),
+            #[cfg(feature = "fbdev")]
+            BackendImpl::Fbdev(b) => b.capture_monitor(monitor_id),
         }
     }
 
-    pub fn capture_monitor(&self, monitor_id: Option<u32>) -> Result<RgbaImage, McpError> {
-        match self {
+    /// Re-probe monitors where the backend caches them. Returns a generation
+    /// counter that changes whenever monitors are added, removed or change mode.
+    pub fn refresh_monitors(&self) -> u64 {
+        match &self.inner {
             #[cfg(feature = "desktop")]
-            Backend::Xcap(b) => b.capture_monitor(monitor_id),
+            BackendImpl::Xcap(_) => 0,
             #[cfg(feature = "kms")]
-            Backend::Kms(b) => b.capture_monitor(monitor_id),
+            BackendImpl::Kms(b) => b.refresh(),
+            #[cfg(feature = "fbdev")]
+            BackendImpl::Fbdev(_) => 0,
         }
     }
 
+    /// Capture every monitor and composite them at their desktop positions into one image
+    /// of the whole virtual desktop. Areas not covered by any monitor are transparent.
+    /// Returns the image, the global coordinates of its top-left corner and its
+    /// pixels per desktop unit.
+    ///
+    /// Monitor positions are logical while captures are physical pixels, so on scaled
+    /// displays the desktop is laid out at the highest monitor scale and captures of
+    /// less scaled monitors are enlarged to match.
+    pub fn capture_all(&self, fresh: bool) -> Result<(RgbaImage, (i32, i32), f64), McpError> {
+        let monitors = self.monitor_layout()?;
+        if monitors.is_empty() {
+            return Err(McpError::internal_error("No monitors found", None));
+        }
+
+        let mut captures = Vec::with_capacity(monitors.len());
+        let mut scale = 0f64;
+        for m in &monitors {
+            let rgba = self.capture_monitor(Some(m.id), fresh)?;
+            scale = scale.max(capture_scale(m.width, rgba.width()));
+            captures.push((m.rect(), rgba));
+        }
+
+        let (canvas, origin) = composite(&captures, scale);
+        Ok((canvas, origin, scale))
+    }
+
+    /// Capture a rectangle relative to the top-left of one monitor.
+    /// The image is clipped to the monitor; clipped parts are reported in `off_screen`.
     pub fn capture_region(
         &self,
         monitor_id: Option<u32>,
@@ -94,75 +426,347 @@ impl Backend {
         y: i32,
         width: u32,
         height: u32,
-    ) -> Result<DynamicImage, McpError> {
-        let rgba = self.capture_monitor(monitor_id)?;
-        let img = DynamicImage::ImageRgba8(rgba);
-
-        let (img_w, img_h) = (img.width(), img.height());
-        let crop_x = x.max(0) as u32;
-        let crop_y = y.max(0) as u32;
-        if crop_x >= img_w || crop_y >= img_h {
+        fresh: bool,
+    ) -> Result<RegionCapture, McpError> {
+        let requested = Self::requested_rect(x, y, width, height)?;
+        let monitor = self.monitor_info(monitor_id)?;
+        let (visible, image, bounds) = self
+            .capture_monitor_part(&monitor, requested, fresh)?
+            .ok_or_else(|| McpError::invalid_params("Region is outside screen bounds", None))?;
+
+        let off_screen = requested
+            .subtract(&bounds)
+            .into_iter()
+            .map(|r| Rect {
+                x: r.x.saturating_add(monitor.x),
+                y: r.y.saturating_add(monitor.y),
+                ..r
+            })
+            .collect();
+
+        Ok(RegionCapture {
+            scale: capture_scale(visible.width, image.width()),
+            image: DynamicImage::ImageRgba8(image),
+            origin: (monitor.x + visible.x, monitor.y + visible.y),
+            off_screen,
+        })
+    }
+
+    /// Capture a rectangle in global desktop coordinates, stitching together the
+    /// pieces from every monitor it intersects. The image spans the bounding box of
+    /// the on-screen pieces; gaps between monitors are transparent.
+    pub fn capture_desktop_region(
+        &self,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        fresh: bool,
+    ) -> Result<RegionCapture, McpError> {
+        let requested = Self::requested_rect(x, y, width, height)?;
+        let mut off_screen = vec![requested];
+        let mut pieces = Vec::new();
+        let mut scale = 0f64;
+
+        for m in self.monitor_layout()? {
+            let monitor_rect = m.rect();
+            let Some(part) = requested.intersect(&monitor_rect) else {
+                continue;
+            };
+            off_screen = off_screen
+                .iter()
+                .flat_map(|r| r.subtract(&monitor_rect))
+                .collect();
+
+            let local = Rect {
+                x: part.x - m.x,
+                y: part.y - m.y,
+                ..part
+            };
+            if let Some((local, piece, _)) = self.capture_monitor_part(&m, local, fresh)? {
+                scale = scale.max(capture_scale(local.width, piece.width()));
+                let global = Rect {
+                    x: local.x + m.x,
+                    y: local.y + m.y,
+                    ..local
+                };
+                pieces.push((global, piece));
+            }
+        }
+
+        if pieces.is_empty() {
+            return Err(McpError::invalid_params(
+                "Region does not intersect any monitor",
+                None,
+            ));
+        }
+
+        let (canvas, origin) = composite(&pieces, scale);
+
+        Ok(RegionCapture {
+            image: DynamicImage::ImageRgba8(canvas),
+            origin,
+            scale,
+            off_screen,
+        })
+    }
+
+    /// Capture the part of `monitor` inside `rect` (monitor-relative, logical units).
+    /// Returns the visible part of `rect`, its pixels and the monitor bounds it was
+    /// clipped to, or `None` when `rect` misses the monitor.
+    fn capture_monitor_part(
+        &self,
+        monitor: &MonitorInfo,
+        rect: Rect,
+        fresh: bool,
+    ) -> Result<Option<(Rect, RgbaImage, Rect)>, McpError> {
+        let bounds = Rect {
+            x: 0,
+            y: 0,
+            width: monitor.width,
+            height: monitor.height,
+        };
+        let Some(visible) = rect.intersect(&bounds) else {
+            return Ok(None);
+        };
+        // A cached full frame serves many crops, so prefer it to native regions
+        // unless the caller wants a fresh frame anyway
+        if self.capabilities().native_region && (self.frame_cache.is_none() || fresh) {
+            let image = self.capture_monitor_rect(monitor.id, visible)?;
+            return Ok(Some((visible, image, bounds)));
+        }
+
+        // Capture the whole monitor and crop
+        let rgba = match &self.frame_cache {
+            Some(cache) => self.cached_frame(cache, Some(monitor.id), fresh)?,
+            None => Arc::new(self.capture_monitor_uncached(Some(monitor.id))?),
+        };
+        Ok(crop_logical(&rgba, visible, monitor.width).map(|image| (visible, image, bounds)))
+    }
+
+    /// Native sub-rectangle capture for backends with `native_region`.
+    #[allow(unused_variables)]
+    fn capture_monitor_rect(&self, monitor_id: u32, rect: Rect) -> Result<RgbaImage, McpError> {
+        match &self.inner {
+            #[cfg(feature = "desktop")]
+            BackendImpl::Xcap(_) => Err(McpError::internal_error(
+                "Native region capture is not supported on desktop backend",
+                None,
+            )),
+            #[cfg(feature = "kms")]
+            BackendImpl::Kms(b) => b.capture_region(monitor_id, rect),
+            #[cfg(feature = "fbdev")]
+            BackendImpl::Fbdev(_) => Err(McpError::internal_error(
+                "Native region capture is not supported on fbdev backend",
+                None,
+            )),
+        }
+    }
+
+    fn requested_rect(x: i32, y: i32, width: u32, height: u32) -> Result<Rect, McpError> {
+        if width == 0 || height == 0 {
             return Err(McpError::invalid_params(
-                "Region is outside screen bounds",
+                "Region width and height must be greater than 0",
                 None,
             ));
         }
-        let crop_w = width.min(img_w - crop_x);
-        let crop_h = height.min(img_h - crop_y);
-        Ok(img.crop_imm(crop_x, crop_y, crop_w, crop_h))
+        Ok(Rect {
+            x,
+            y,
+            width,
+            height,
+        })
     }
 
+    /// Capture the single window matching `selector`, returning its info alongside the image.
     #[allow(unused_variables)]
-    pub fn capture_window(&self, window_id: u32) -> Result<RgbaImage, McpError> {
-        match self {
+    pub fn capture_window(
+        &self,
+        selector: &WindowSelector,
+    ) -> Result<(WindowInfo, RgbaImage), McpError> {
+        if selector.is_empty() {
+            return Err(McpError::invalid_params(
+                "No window selector given: set window_id, title, title_regex, app_name, pid or focused",
+                None,
+            ));
+        }
+        match &self.inner {
             #[cfg(feature = "desktop")]
-            Backend::Xcap(b) => b.capture_window(window_id),
+            BackendImpl::Xcap(b) => b.capture_window(selector),
             #[cfg(feature = "kms")]
-            Backend::Kms(_) => Err(McpError::internal_error(
+            BackendImpl::Kms(_) => Err(McpError::internal_error(
                 "Window capture is not supported on KMS backend",
                 None,
             )),
+            #[cfg(feature = "fbdev")]
+            BackendImpl::Fbdev(_) => Err(McpError::internal_error(
+                "Window capture is not supported on fbdev backend",
+                None,
+            )),
         }
     }
 
     pub fn list_windows(&self) -> Result<Vec<WindowInfo>, McpError> {
-        match self {
+        match &self.inner {
             #[cfg(feature = "desktop")]
-            Backend::Xcap(b) => b.list_windows(),
+            BackendImpl::Xcap(b) => b.list_windows(),
             #[cfg(feature = "kms")]
-            Backend::Kms(_) => Err(McpError::internal_error(
+            BackendImpl::Kms(_) => Err(McpError::internal_error(
                 "Window listing is not supported on KMS backend",
                 None,
             )),
+            #[cfg(feature = "fbdev")]
+            BackendImpl::Fbdev(_) => Err(McpError::internal_error(
+                "Window listing is not supported on fbdev backend",
+                None,
+            )),
         }
     }
 
     pub fn list_monitors(&self) -> Result<Vec<MonitorInfo>, McpError> {
-        match self {
+        match &self.inner {
+            #[cfg(feature = "desktop")]
+            BackendImpl::Xcap(b) => b.list_monitors(),
+            #[cfg(feature = "kms")]
+            BackendImpl::Kms(b) => b.list_monitors(),
+            #[cfg(feature = "fbdev")]
+            BackendImpl::Fbdev(b) => b.list_monitors(),
+        }
+    }
+
+    /// Monitor IDs, names, positions and sizes, without the metadata `list_monitors`
+    /// gathers (EDID, physical size, ...), which capture paths do not need.
+    pub fn monitor_layout(&self) -> Result<Vec<MonitorInfo>, McpError> {
+        match &self.inner {
             #[cfg(feature = "desktop")]
-            Backend::Xcap(b) => b.list_monitors(),
+            BackendImpl::Xcap(b) => b.list_monitors(),
             #[cfg(feature = "kms")]
-            Backend::Kms(b) => b.list_monitors(),
+            BackendImpl::Kms(b) => b.monitor_layout(),
+            #[cfg(feature = "fbdev")]
+            BackendImpl::Fbdev(b) => b.list_monitors(),
+        }
+    }
+
+    /// Look up a monitor's geometry the same way `capture_monitor` resolves it
+    /// (explicit ID, else primary, else first).
+    pub fn monitor_info(&self, monitor_id: Option<u32>) -> Result<MonitorInfo, McpError> {
+        let monitors = self.monitor_layout()?;
+        match monitor_id {
+            Some(id) => monitors.into_iter().find(|m| m.id == id).ok_or_else(|| {
+                McpError::invalid_params(format!("Monitor with ID {id} not found"), None)
+            }),
+            None => {
+                let primary = monitors.iter().position(|m| m.is_primary).unwrap_or(0);
+                monitors
+                    .into_iter()
+                    .nth(primary)
+                    .ok_or_else(|| McpError::internal_error("No monitors found", None))
+            }
+        }
+    }
+}
+
+/// Paste images covering logical rectangles (global desktop coordinates) onto one
+/// transparent canvas spanning their bounding box at `scale` pixels per logical
+/// pixel. An image whose size differs from its scaled rectangle is resized to fit.
+/// Returns the canvas and the logical position of its top-left corner.
+fn composite(pieces: &[(Rect, RgbaImage)], scale: f64) -> (RgbaImage, (i32, i32)) {
+    let min_x = pieces.iter().map(|(r, _)| r.x).min().unwrap_or(0);
+    let min_y = pieces.iter().map(|(r, _)| r.y).min().unwrap_or(0);
+    let max_x = pieces.iter().map(|(r, _)| r.right()).max().unwrap_or(0);
+    let max_y = pieces.iter().map(|(r, _)| r.bottom()).max().unwrap_or(0);
+    let bounds = Rect {
+        x: 0,
+        y: 0,
+        width: (max_x - min_x as i64) as u32,
+        height: (max_y - min_y as i64) as u32,
+    }
+    .scaled(scale);
+
+    let mut canvas = RgbaImage::new(bounds.width, bounds.height);
+    for (rect, img) in pieces {
+        let target = Rect {
+            x: rect.x - min_x,
+            y: rect.y - min_y,
+            ..*rect
+        }
+        .scaled(scale);
+        let (x, y) = (target.x as i64, target.y as i64);
+        if img.dimensions() == (target.width, target.height) {
+            imageops::replace(&mut canvas, img, x, y);
+        } else {
+            let resized = imageops::resize(
+                img,
+                target.width,
+                target.height,
+                imageops::FilterType::Triangle,
+            );
+            imageops::replace(&mut canvas, &resized, x, y);
         }
     }
+    (canvas, (min_x, min_y))
+}
+
+/// Crop `rect`, in logical units relative to the monitor, out of `capture`, a
+/// capture of a monitor `logical_width` units wide. On scaled displays the capture
+/// is in physical pixels, so `rect` is scaled to match and rounded outwards.
+fn crop_logical(capture: &RgbaImage, rect: Rect, logical_width: u32) -> Option<RgbaImage> {
+    let (width, height) = capture.dimensions();
+    let pixels = rect
+        .scaled(capture_scale(logical_width, width))
+        .intersect(&Rect {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        })?;
+    Some(
+        imageops::crop_imm(
+            capture,
+            pixels.x as u32,
+            pixels.y as u32,
+            pixels.width,
+            pixels.height,
+        )
+        .to_image(),
+    )
+}
+
+/// Physical pixels per logical pixel of a capture `pixels` wide covering `logical`
+/// desktop units. Measured from the capture rather than taken from `scale_factor`,
+/// which not every backend reports.
+pub fn capture_scale(logical: u32, pixels: u32) -> f64 {
+    if logical == 0 {
+        1.0
+    } else {
+        pixels as f64 / logical as f64
+    }
 }
 
 // -- Backend detection --
 
-pub fn detect() -> Result<Backend, Box<dyn std::error::Error>> {
+/// Pick a backend. `primary_output` names the KMS connector to treat as the primary monitor.
+#[cfg_attr(not(feature = "kms"), allow(unused_variables))]
+pub fn detect(primary_output: Option<&str>) -> Result<Backend, Box<dyn std::error::Error>> {
     // 1. Check env override
     if let Ok(val) = std::env::var("MCP_SCREENSHOT_BACKEND") {
         match val.as_str() {
             #[cfg(feature = "desktop")]
             "desktop" => {
                 tracing::info!("Using desktop backend (env override)");
-                return Ok(Backend::Xcap(XcapBackend));
+                return Ok(BackendImpl::Xcap(XcapBackend).into());
             }
             #[cfg(feature = "kms")]
             "kms" => {
                 tracing::info!("Using KMS backend (env override)");
-                let b = KmsBackend::open()?;
-                return Ok(Backend::Kms(b));
+                let b = KmsBackend::open(primary_output)?;
+                return Ok(BackendImpl::Kms(b).into());
+            }
+            #[cfg(feature = "fbdev")]
+            "fbdev" => {
+                tracing::info!("Using fbdev backend (env override)");
+                let b = FbdevBackend::open()?;
+                return Ok(BackendImpl::Fbdev(b).into());
             }
             other => {
                 return Err(format!("Unknown backend '{other}' in MCP_SCREENSHOT_BACKEND").into());
@@ -177,31 +781,171 @@ pub fn detect() -> Result<Backend, Box<dyn std::error::Error>> {
             || std::env::var_os("WAYLAND_DISPLAY").is_some()
         {
             tracing::info!("Display server detected, using xcap backend");
-            return Ok(Backend::Xcap(XcapBackend));
+            return Ok(BackendImpl::Xcap(XcapBackend).into());
         }
     }
 
     // 3. Try KMS
     #[cfg(feature = "kms")]
-    {
-        match KmsBackend::open() {
-            Ok(b) => {
-                tracing::info!("Using KMS backend (no display server found)");
-                return Ok(Backend::Kms(b));
-            }
-            Err(e) => {
-                tracing::debug!("KMS probe failed: {e}");
-            }
+    #[cfg_attr(feature = "desktop", allow(unused_variables))]
+    let kms_error = match KmsBackend::open(primary_output) {
+        Ok(b) => {
+            tracing::info!("Using KMS backend (no display server found)");
+            return Ok(BackendImpl::Kms(b).into());
         }
-    }
+        Err(e) => {
+            tracing::debug!("KMS probe failed: {e}");
+            e
+        }
+    };
 
-    // 4. Fallback to xcap even without display env vars
+    // 4. Try fbdev (boards without DRM)
+    #[cfg(feature = "fbdev")]
+    #[cfg_attr(feature = "desktop", allow(unused_variables))]
+    let fbdev_error = match FbdevBackend::open() {
+        Ok(b) => {
+            tracing::info!("Using fbdev backend (no display server or KMS output found)");
+            return Ok(BackendImpl::Fbdev(b).into());
+        }
+        Err(e) => {
+            tracing::debug!("fbdev probe failed: {e}");
+            e
+        }
+    };
+
+    // 5. Fallback to xcap even without display env vars
     #[cfg(feature = "desktop")]
     {
         tracing::info!("Falling back to xcap backend");
-        return Ok(Backend::Xcap(XcapBackend));
+        return Ok(BackendImpl::Xcap(XcapBackend).into());
+    }
+
+    #[cfg(not(feature = "desktop"))]
+    {
+        let mut errors: Vec<String> = Vec::new();
+        #[cfg(feature = "kms")]
+        errors.push(format!("KMS backend unavailable: {kms_error}"));
+        #[cfg(feature = "fbdev")]
+        errors.push(format!("fbdev backend unavailable: {fbdev_error}"));
+        if !errors.is_empty() {
+            return Err(errors.join("; ").into());
+        }
     }
 
     #[allow(unreachable_code)]
-    Err("No usable backend found. Enable the 'desktop' or 'kms' feature.".into())
+    Err("No usable backend found. Enable the 'desktop', 'kms' or 'fbdev' feature.".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, image::Rgba(color))
+    }
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    #[test]
+    fn intersect_clips_to_the_overlap() {
+        let monitor = rect(1920, 0, 1280, 1024);
+        assert_eq!(
+            rect(1800, -10, 200, 100).intersect(&monitor),
+            Some(rect(1920, 0, 80, 90))
+        );
+        assert_eq!(monitor.intersect(&rect(100, 100, 50, 50)), None);
+        // Touching edges do not overlap
+        assert_eq!(rect(0, 0, 1920, 1080).intersect(&monitor), None);
+    }
+
+    #[test]
+    fn subtract_leaves_the_uncovered_parts() {
+        let region = rect(0, 0, 100, 100);
+        assert_eq!(region.subtract(&rect(200, 200, 10, 10)), [region]);
+        assert_eq!(region.subtract(&rect(-10, -10, 200, 200)), []);
+        // A hole in the middle leaves strips above, below, left and right
+        assert_eq!(
+            region.subtract(&rect(25, 40, 50, 20)),
+            [
+                rect(0, 0, 100, 40),
+                rect(0, 60, 100, 40),
+                rect(0, 40, 25, 20),
+                rect(75, 40, 25, 20)
+            ]
+        );
+    }
+
+    #[test]
+    fn scaled_rounds_outwards() {
+        assert_eq!(rect(10, 20, 30, 40).scaled(2.0), rect(20, 40, 60, 80));
+        assert_eq!(rect(1, 1, 1, 1).scaled(1.5), rect(1, 1, 2, 2));
+    }
+
+    #[test]
+    fn crop_logical_scales_to_physical_pixels() {
+        // A 100x50-point monitor captured at 2x, red on the left half, blue on the right
+        let mut capture = solid(200, 100, RED);
+        imageops::replace(&mut capture, &solid(100, 100, BLUE), 100, 0);
+        let crop = crop_logical(&capture, rect(40, 10, 20, 10), 100).unwrap();
+        assert_eq!(crop.dimensions(), (40, 20));
+        assert_eq!(crop.get_pixel(19, 0).0, RED);
+        assert_eq!(crop.get_pixel(20, 0).0, BLUE);
+        // Unscaled captures are cropped as they are
+        let crop = crop_logical(&capture, rect(90, 0, 20, 10), 200).unwrap();
+        assert_eq!(crop.dimensions(), (20, 10));
+    }
+
+    #[test]
+    fn composite_leaves_gaps_transparent() {
+        let pieces = [
+            (rect(0, 0, 10, 10), solid(10, 10, RED)),
+            (rect(20, 5, 10, 10), solid(10, 10, BLUE)),
+        ];
+        let (canvas, origin) = composite(&pieces, 1.0);
+        assert_eq!(origin, (0, 0));
+        assert_eq!(canvas.dimensions(), (30, 15));
+        assert_eq!(canvas.get_pixel(15, 5).0, [0, 0, 0, 0]);
+        assert_eq!(canvas.get_pixel(0, 14).0, [0, 0, 0, 0]);
+        assert_eq!(canvas.get_pixel(25, 14).0, BLUE);
+    }
+
+    #[test]
+    fn composite_places_scaled_monitors_side_by_side() {
+        // Two 100x50-point monitors captured at 2x
+        let pieces = [
+            (rect(0, 0, 100, 50), solid(200, 100, RED)),
+            (rect(100, 0, 100, 50), solid(200, 100, BLUE)),
+        ];
+        let (canvas, origin) = composite(&pieces, 2.0);
+        assert_eq!(origin, (0, 0));
+        assert_eq!(canvas.dimensions(), (400, 100));
+        assert_eq!(canvas.get_pixel(199, 99).0, RED);
+        assert_eq!(canvas.get_pixel(200, 0).0, BLUE);
+        assert_eq!(canvas.get_pixel(399, 99).0, BLUE);
+    }
+
+    #[test]
+    fn composite_enlarges_less_scaled_monitors() {
+        // A 2x monitor next to a 1x one, laid out at 2x
+        let pieces = [
+            (rect(-100, 0, 100, 50), solid(200, 100, RED)),
+            (rect(0, 0, 100, 50), solid(100, 50, BLUE)),
+        ];
+        let (canvas, origin) = composite(&pieces, 2.0);
+        assert_eq!(origin, (-100, 0));
+        assert_eq!(canvas.dimensions(), (400, 100));
+        assert_eq!(canvas.get_pixel(199, 0).0, RED);
+        assert_eq!(canvas.get_pixel(200, 0).0, BLUE);
+        assert_eq!(canvas.get_pixel(399, 99).0, BLUE);
+    }
 }
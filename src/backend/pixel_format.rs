@@ -1,5 +1,12 @@
 use drm_fourcc::DrmFourcc;
 
+/// One plane of a framebuffer: its bytes from the plane offset on, and its pitch.
+#[cfg(feature = "kms")]
+pub struct Plane<'a> {
+    pub data: &'a [u8],
+    pub pitch: u32,
+}
+
 /// Convert raw framebuffer pixels to RGBA8888 format.
 ///
 /// `src` is the raw pixel data, `width`/`height` are in pixels,
@@ -18,10 +25,111 @@ pub fn convert_to_rgba(
         DrmFourcc::Xbgr8888 => convert_xbgr8888(src, width, height, pitch),
         DrmFourcc::Abgr8888 => convert_abgr8888(src, width, height, pitch),
         DrmFourcc::Rgb565 => convert_rgb565(src, width, height, pitch),
+        // [X, B, G, R] and [A, B, G, R] in memory
+        DrmFourcc::Rgbx8888 => convert_packed::<4>(src, width, height, pitch, |p| [p[3], p[2], p[1], 0xFF]),
+        DrmFourcc::Rgba8888 => convert_packed::<4>(src, width, height, pitch, |p| [p[3], p[2], p[1], p[0]]),
+        // [X, R, G, B] and [A, R, G, B] in memory
+        DrmFourcc::Bgrx8888 => convert_packed::<4>(src, width, height, pitch, |p| [p[1], p[2], p[3], 0xFF]),
+        DrmFourcc::Bgra8888 => convert_packed::<4>(src, width, height, pitch, |p| [p[1], p[2], p[3], p[0]]),
+        // [B, G, R] and [R, G, B] in memory
+        DrmFourcc::Rgb888 => convert_packed::<3>(src, width, height, pitch, |p| [p[2], p[1], p[0], 0xFF]),
+        DrmFourcc::Bgr888 => convert_packed::<3>(src, width, height, pitch, |p| [p[0], p[1], p[2], 0xFF]),
+        DrmFourcc::Xrgb2101010 => convert_packed::<4>(src, width, height, pitch, |p| {
+            let [r, g, b, _] = unpack_2101010(p);
+            [r, g, b, 0xFF]
+        }),
+        DrmFourcc::Argb2101010 => convert_packed::<4>(src, width, height, pitch, unpack_2101010),
+        DrmFourcc::Xbgr2101010 => convert_packed::<4>(src, width, height, pitch, |p| {
+            let [b, g, r, _] = unpack_2101010(p);
+            [r, g, b, 0xFF]
+        }),
+        DrmFourcc::Abgr2101010 => convert_packed::<4>(src, width, height, pitch, |p| {
+            let [b, g, r, a] = unpack_2101010(p);
+            [r, g, b, a]
+        }),
+        DrmFourcc::Xrgb1555 => convert_packed::<2>(src, width, height, pitch, |p| {
+            let [r, g, b, _] = unpack_1555(p);
+            [r, g, b, 0xFF]
+        }),
+        DrmFourcc::Argb1555 => convert_packed::<2>(src, width, height, pitch, unpack_1555),
+        DrmFourcc::Yuyv => convert_yuyv(src, width, height, pitch),
+        DrmFourcc::Nv12 | DrmFourcc::Nv21 => {
+            Err(format!("{format:?} has two planes; use convert_planes_to_rgba"))
+        }
         other => Err(format!("Unsupported pixel format: {other:?}")),
     }
 }
 
+/// Convert a framebuffer given as separate planes. Single-plane formats use the
+/// first plane and behave like `convert_to_rgba`.
+#[cfg(feature = "kms")]
+pub fn convert_planes_to_rgba(
+    planes: &[Plane],
+    width: u32,
+    height: u32,
+    format: DrmFourcc,
+) -> Result<Vec<u8>, String> {
+    match (format, planes) {
+        (DrmFourcc::Nv12 | DrmFourcc::Nv21, [luma, chroma, ..]) => {
+            convert_nv12(luma, chroma, width, height, format == DrmFourcc::Nv21)
+        }
+        (_, [plane, ..]) if plane_count(format) == planes.len() => {
+            convert_to_rgba(plane.data, width, height, plane.pitch, format)
+        }
+        _ => Err(format!(
+            "{format:?} needs {} plane(s), got {}",
+            plane_count(format),
+            planes.len()
+        )),
+    }
+}
+
+/// Number of planes `convert_planes_to_rgba` expects for `format`.
+#[cfg(feature = "kms")]
+pub fn plane_count(format: DrmFourcc) -> usize {
+    match format {
+        DrmFourcc::Nv12 | DrmFourcc::Nv21 => 2,
+        _ => 1,
+    }
+}
+
+/// Rows in plane `index` of a `height`-row frame (chroma planes are subsampled).
+#[cfg(feature = "kms")]
+pub fn plane_height(format: DrmFourcc, index: usize, height: u32) -> u32 {
+    match (format, index) {
+        (DrmFourcc::Nv12 | DrmFourcc::Nv21, 1) => height.div_ceil(2),
+        _ => height,
+    }
+}
+
+/// Whether `convert_to_rgba` or `convert_planes_to_rgba` can handle `format`.
+#[cfg(feature = "kms")]
+pub fn is_supported(format: DrmFourcc) -> bool {
+    bytes_per_pixel(format).is_some() || plane_count(format) > 1
+}
+
+/// Bytes per pixel of the single-plane formats `convert_to_rgba` handles.
+#[cfg(feature = "kms")]
+pub fn bytes_per_pixel(format: DrmFourcc) -> Option<u32> {
+    match format {
+        DrmFourcc::Xrgb8888
+        | DrmFourcc::Argb8888
+        | DrmFourcc::Xbgr8888
+        | DrmFourcc::Abgr8888
+        | DrmFourcc::Rgbx8888
+        | DrmFourcc::Rgba8888
+        | DrmFourcc::Bgrx8888
+        | DrmFourcc::Bgra8888
+        | DrmFourcc::Xrgb2101010
+        | DrmFourcc::Argb2101010
+        | DrmFourcc::Xbgr2101010
+        | DrmFourcc::Abgr2101010 => Some(4),
+        DrmFourcc::Rgb888 | DrmFourcc::Bgr888 => Some(3),
+        DrmFourcc::Rgb565 | DrmFourcc::Xrgb1555 | DrmFourcc::Argb1555 | DrmFourcc::Yuyv => Some(2),
+        _ => None,
+    }
+}
+
 /// XRGB8888: memory layout [B, G, R, X] per pixel (little-endian u32 = 0xXXRRGGBB)
 fn convert_xrgb8888(src: &[u8], width: u32, height: u32, pitch: u32) -> Result<Vec<u8>, String> {
     let mut dst = Vec::with_capacity((width * height * 4) as usize);
@@ -108,3 +216,373 @@ fn convert_rgb565(src: &[u8], width: u32, height: u32, pitch: u32) -> Result<Vec
     }
     Ok(dst)
 }
+
+/// Convert a packed format of `BPP` bytes per pixel with a per-pixel mapping to RGBA.
+fn convert_packed<const BPP: usize>(
+    src: &[u8],
+    width: u32,
+    height: u32,
+    pitch: u32,
+    to_rgba: impl Fn(&[u8]) -> [u8; 4],
+) -> Result<Vec<u8>, String> {
+    let row_bytes = width as usize * BPP;
+    check_plane_size(src, row_bytes, height, pitch)?;
+
+    let mut dst = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        let row = &src[(y * pitch) as usize..][..row_bytes];
+        for px in row.chunks_exact(BPP) {
+            dst.extend_from_slice(&to_rgba(px));
+        }
+    }
+    Ok(dst)
+}
+
+fn check_plane_size(src: &[u8], row_bytes: usize, height: u32, pitch: u32) -> Result<(), String> {
+    if height > 0 && src.len() < (height as usize - 1) * pitch as usize + row_bytes {
+        return Err("Framebuffer is smaller than its reported geometry".to_string());
+    }
+    Ok(())
+}
+
+/// ARGB2101010 little-endian word (0bAARRRRRRRRRRGGGGGGGGGGBBBBBBBBBB) to 8-bit
+/// [R, G, B, A]; swap R and B for the ABGR variant.
+fn unpack_2101010(p: &[u8]) -> [u8; 4] {
+    let v = u32::from_le_bytes([p[0], p[1], p[2], p[3]]);
+    [
+        (v >> 22) as u8,
+        (v >> 12) as u8,
+        (v >> 2) as u8,
+        ((v >> 30) * 0x55) as u8,
+    ]
+}
+
+/// ARGB1555 little-endian u16 to 8-bit [R, G, B, A].
+fn unpack_1555(p: &[u8]) -> [u8; 4] {
+    let v = u16::from_le_bytes([p[0], p[1]]);
+    let expand = |c: u16| ((c << 3) | (c >> 2)) as u8;
+    [
+        expand((v >> 10) & 0x1F),
+        expand((v >> 5) & 0x1F),
+        expand(v & 0x1F),
+        if v & 0x8000 != 0 { 0xFF } else { 0 },
+    ]
+}
+
+/// BT.601 limited-range YCbCr to RGBA, the usual encoding of video planes.
+fn yuv_to_rgba(y: u8, u: u8, v: u8) -> [u8; 4] {
+    let c = (y as i32 - 16) * 298;
+    let d = u as i32 - 128;
+    let e = v as i32 - 128;
+    let clamp = |x: i32| ((x + 128) >> 8).clamp(0, 255) as u8;
+    [
+        clamp(c + 409 * e),
+        clamp(c - 100 * d - 208 * e),
+        clamp(c + 516 * d),
+        0xFF,
+    ]
+}
+
+/// YUYV: memory layout [Y0, U, Y1, V] per pair of pixels sharing chroma
+fn convert_yuyv(src: &[u8], width: u32, height: u32, pitch: u32) -> Result<Vec<u8>, String> {
+    let row_bytes = width.div_ceil(2) as usize * 4;
+    check_plane_size(src, row_bytes, height, pitch)?;
+
+    let mut dst = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        let row = &src[(y * pitch) as usize..][..row_bytes];
+        for x in 0..width as usize {
+            let pair = &row[x / 2 * 4..][..4];
+            let luma = pair[(x % 2) * 2];
+            dst.extend_from_slice(&yuv_to_rgba(luma, pair[1], pair[3]));
+        }
+    }
+    Ok(dst)
+}
+
+/// NV12: full-resolution Y plane, then a half-resolution plane of interleaved
+/// [U, V] pairs ([V, U] for NV21)
+#[cfg(feature = "kms")]
+fn convert_nv12(
+    luma: &Plane,
+    chroma: &Plane,
+    width: u32,
+    height: u32,
+    swap_uv: bool,
+) -> Result<Vec<u8>, String> {
+    check_plane_size(luma.data, width as usize, height, luma.pitch)?;
+    check_plane_size(
+        chroma.data,
+        width.div_ceil(2) as usize * 2,
+        height.div_ceil(2),
+        chroma.pitch,
+    )?;
+
+    let mut dst = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        let y_row = &luma.data[(y * luma.pitch) as usize..];
+        let uv_row = &chroma.data[(y / 2 * chroma.pitch) as usize..];
+        for x in 0..width as usize {
+            let (mut u, mut v) = (uv_row[x / 2 * 2], uv_row[x / 2 * 2 + 1]);
+            if swap_uv {
+                std::mem::swap(&mut u, &mut v);
+            }
+            dst.extend_from_slice(&yuv_to_rgba(y_row[x], u, v));
+        }
+    }
+    Ok(dst)
+}
+
+// -- Bitfield layouts (fbdev) --
+
+/// Position and width of one channel within a pixel word, as in fbdev's `fb_bitfield`.
+#[cfg(feature = "fbdev")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Channel {
+    pub offset: u32,
+    pub length: u32,
+}
+
+/// Pixel layout given as per-channel bitfields within a little-endian pixel word.
+/// An alpha channel of length 0 means the pixels are opaque.
+#[cfg(feature = "fbdev")]
+#[derive(Debug, Clone, Copy)]
+pub struct BitfieldLayout {
+    pub bits_per_pixel: u32,
+    pub red: Channel,
+    pub green: Channel,
+    pub blue: Channel,
+    pub alpha: Channel,
+}
+
+#[cfg(feature = "fbdev")]
+impl BitfieldLayout {
+    /// The DRM format with the same memory layout, if any, so the specialised
+    /// converters above can be used instead of the generic bitfield path.
+    pub fn fourcc(&self) -> Option<DrmFourcc> {
+        let ch = |offset, length| Channel { offset, length };
+        let rgb = (self.bits_per_pixel, self.red, self.green, self.blue);
+        let opaque = self.alpha.length == 0;
+        let alpha24 = self.alpha == ch(24, 8);
+
+        if rgb == (32, ch(16, 8), ch(8, 8), ch(0, 8)) {
+            if opaque {
+                return Some(DrmFourcc::Xrgb8888);
+            } else if alpha24 {
+                return Some(DrmFourcc::Argb8888);
+            }
+        }
+        if rgb == (32, ch(0, 8), ch(8, 8), ch(16, 8)) {
+            if opaque {
+                return Some(DrmFourcc::Xbgr8888);
+            } else if alpha24 {
+                return Some(DrmFourcc::Abgr8888);
+            }
+        }
+        if rgb == (24, ch(16, 8), ch(8, 8), ch(0, 8)) && opaque {
+            return Some(DrmFourcc::Rgb888);
+        }
+        if rgb == (24, ch(0, 8), ch(8, 8), ch(16, 8)) && opaque {
+            return Some(DrmFourcc::Bgr888);
+        }
+        if rgb == (16, ch(11, 5), ch(5, 6), ch(0, 5)) && opaque {
+            return Some(DrmFourcc::Rgb565);
+        }
+        if rgb == (16, ch(10, 5), ch(5, 5), ch(0, 5)) && opaque {
+            return Some(DrmFourcc::Xrgb1555);
+        }
+        None
+    }
+}
+
+#[cfg(feature = "fbdev")]
+impl std::fmt::Display for BitfieldLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}bpp R{}:{} G{}:{} B{}:{} A{}:{}",
+            self.bits_per_pixel,
+            self.red.offset,
+            self.red.length,
+            self.green.offset,
+            self.green.length,
+            self.blue.offset,
+            self.blue.length,
+            self.alpha.offset,
+            self.alpha.length,
+        )
+    }
+}
+
+/// Convert pixels with an arbitrary bitfield layout to RGBA8888, scaling each
+/// channel to 8 bits. Slower than `convert_to_rgba`; use it when `layout.fourcc()`
+/// has no match.
+#[cfg(feature = "fbdev")]
+pub fn convert_bitfields(
+    src: &[u8],
+    width: u32,
+    height: u32,
+    pitch: u32,
+    layout: &BitfieldLayout,
+) -> Result<Vec<u8>, String> {
+    let bytes_per_pixel = match layout.bits_per_pixel {
+        8 | 16 | 24 | 32 => (layout.bits_per_pixel / 8) as usize,
+        other => return Err(format!("Unsupported bits per pixel: {other}")),
+    };
+    let channels = [layout.red, layout.green, layout.blue, layout.alpha];
+    for ch in channels {
+        if ch.length > 16 || ch.offset + ch.length > layout.bits_per_pixel {
+            return Err(format!("Invalid pixel layout: {layout}"));
+        }
+    }
+    let row_bytes = width as usize * bytes_per_pixel;
+    if height > 0 && src.len() < (height as usize - 1) * pitch as usize + row_bytes {
+        return Err("Framebuffer is smaller than its reported geometry".to_string());
+    }
+
+    let mut dst = Vec::with_capacity((width * height * 4) as usize);
+    for y in 0..height {
+        let row = &src[(y * pitch) as usize..][..row_bytes];
+        for px in row.chunks_exact(bytes_per_pixel) {
+            let word = px
+                .iter()
+                .rev()
+                .fold(0u32, |acc, &b| (acc << 8) | b as u32);
+            dst.push(extract_channel(word, layout.red));
+            dst.push(extract_channel(word, layout.green));
+            dst.push(extract_channel(word, layout.blue));
+            if layout.alpha.length == 0 {
+                dst.push(0xFF);
+            } else {
+                dst.push(extract_channel(word, layout.alpha));
+            }
+        }
+    }
+    Ok(dst)
+}
+
+/// Pull one channel out of a pixel word and scale it to 0..=255.
+#[cfg(feature = "fbdev")]
+fn extract_channel(word: u32, ch: Channel) -> u8 {
+    if ch.length == 0 {
+        return 0;
+    }
+    let max = (1u32 << ch.length) - 1;
+    let value = (word >> ch.offset) & max;
+    ((value * 255 + max / 2) / max) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Convert one pixel.
+    fn pixel(format: DrmFourcc, bytes: &[u8]) -> [u8; 4] {
+        let rgba = convert_to_rgba(bytes, 1, 1, bytes.len() as u32, format).unwrap();
+        rgba.try_into().unwrap()
+    }
+
+    #[test]
+    fn byte_order_32bit() {
+        assert_eq!(
+            pixel(DrmFourcc::Rgbx8888, &[0x00, 0x30, 0x20, 0x10]),
+            [0x10, 0x20, 0x30, 0xFF]
+        );
+        assert_eq!(
+            pixel(DrmFourcc::Rgba8888, &[0x40, 0x30, 0x20, 0x10]),
+            [0x10, 0x20, 0x30, 0x40]
+        );
+        assert_eq!(
+            pixel(DrmFourcc::Bgrx8888, &[0x00, 0x10, 0x20, 0x30]),
+            [0x10, 0x20, 0x30, 0xFF]
+        );
+        assert_eq!(
+            pixel(DrmFourcc::Bgra8888, &[0x40, 0x10, 0x20, 0x30]),
+            [0x10, 0x20, 0x30, 0x40]
+        );
+    }
+
+    #[test]
+    fn packed_24bit() {
+        assert_eq!(
+            pixel(DrmFourcc::Rgb888, &[0x30, 0x20, 0x10]),
+            [0x10, 0x20, 0x30, 0xFF]
+        );
+        assert_eq!(
+            pixel(DrmFourcc::Bgr888, &[0x10, 0x20, 0x30]),
+            [0x10, 0x20, 0x30, 0xFF]
+        );
+    }
+
+    #[test]
+    fn ten_bit_channels_keep_their_top_bits() {
+        // High channel 0x3FF, middle 0x200, low 0; top two bits are alpha
+        let opaque = [0x00, 0x00, 0xF8, 0x3F];
+        assert_eq!(
+            pixel(DrmFourcc::Xrgb2101010, &opaque),
+            [0xFF, 0x80, 0x00, 0xFF]
+        );
+        assert_eq!(
+            pixel(DrmFourcc::Xbgr2101010, &opaque),
+            [0x00, 0x80, 0xFF, 0xFF]
+        );
+        assert_eq!(
+            pixel(DrmFourcc::Argb2101010, &[0x00, 0x00, 0xF8, 0xBF]),
+            [0xFF, 0x80, 0x00, 0xAA]
+        );
+        assert_eq!(
+            pixel(DrmFourcc::Abgr2101010, &[0x00, 0x00, 0xF8, 0x7F]),
+            [0x00, 0x80, 0xFF, 0x55]
+        );
+    }
+
+    #[test]
+    fn rgb1555_expands_to_eight_bits() {
+        // R 31, G 16, B 0, with and without the top (alpha) bit
+        assert_eq!(
+            pixel(DrmFourcc::Xrgb1555, &[0x00, 0x7E]),
+            [0xFF, 0x84, 0x00, 0xFF]
+        );
+        assert_eq!(
+            pixel(DrmFourcc::Xrgb1555, &[0x00, 0xFE]),
+            [0xFF, 0x84, 0x00, 0xFF]
+        );
+        assert_eq!(
+            pixel(DrmFourcc::Argb1555, &[0x00, 0x7E]),
+            [0xFF, 0x84, 0x00, 0x00]
+        );
+        assert_eq!(
+            pixel(DrmFourcc::Argb1555, &[0x00, 0xFE]),
+            [0xFF, 0x84, 0x00, 0xFF]
+        );
+    }
+
+    #[test]
+    fn yuyv_odd_width_uses_the_last_pair() {
+        // Black and white sharing neutral chroma, then BT.601 red with an unused second luma
+        let row = [16, 128, 235, 128, 81, 90, 0, 240];
+        let rgba = convert_to_rgba(&row, 3, 1, row.len() as u32, DrmFourcc::Yuyv).unwrap();
+        assert_eq!(rgba, [0, 0, 0, 0xFF, 255, 255, 255, 0xFF, 255, 0, 0, 0xFF]);
+    }
+
+    #[cfg(feature = "kms")]
+    #[test]
+    fn nv12_and_nv21_swap_chroma() {
+        let luma = [81, 81, 81, 81];
+        let chroma = [90, 240];
+        let planes = [
+            Plane {
+                data: &luma,
+                pitch: 2,
+            },
+            Plane {
+                data: &chroma,
+                pitch: 2,
+            },
+        ];
+        let nv12 = convert_planes_to_rgba(&planes, 2, 2, DrmFourcc::Nv12).unwrap();
+        let nv21 = convert_planes_to_rgba(&planes, 2, 2, DrmFourcc::Nv21).unwrap();
+        assert_eq!(nv12, [255, 0, 0, 0xFF].repeat(4));
+        assert_eq!(nv21, [15, 63, 255, 0xFF].repeat(4));
+    }
+}
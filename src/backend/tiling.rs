@@ -0,0 +1,305 @@
+use drm_fourcc::DrmModifier;
+
+/// Tiled memory layouts that can be rearranged into linear rows on the CPU.
+///
+/// Layouts that also compress (Intel CCS, AFBC) or interleave tiles in larger
+/// groups (Vivante super-tiling) are not supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tiling {
+    /// Intel X-tiling: 4 KiB tiles of 512 bytes x 8 rows, each row linear.
+    IntelX,
+    /// Intel Y-tiling: 4 KiB tiles of 128 bytes x 32 rows, stored as eight
+    /// 16-byte-wide columns.
+    IntelY,
+    /// Broadcom VC4 T-format: 4 KiB tiles of four 1 KiB subtiles, each 4x4
+    /// micro-tiles of 64 bytes; odd tile rows run right to left.
+    Vc4T,
+    /// Vivante tiling: 4x4 pixel tiles, row-major.
+    Vivante,
+}
+
+impl Tiling {
+    /// `None` for linear buffers and for layouts we cannot detile.
+    pub fn from_modifier(modifier: DrmModifier) -> Option<Self> {
+        match modifier {
+            DrmModifier::I915_x_tiled => Some(Tiling::IntelX),
+            DrmModifier::I915_y_tiled => Some(Tiling::IntelY),
+            DrmModifier::Broadcom_vc4_t_tiled => Some(Tiling::Vc4T),
+            DrmModifier::Vivante_tiled => Some(Tiling::Vivante),
+            _ => None,
+        }
+    }
+
+    /// Width of one tile in bytes; the pitch must be a multiple of it.
+    fn tile_width(&self, cpp: u32) -> u32 {
+        match self {
+            Tiling::IntelX => 512,
+            Tiling::IntelY => 128,
+            Tiling::Vc4T => 8 * vc4_utile_width(cpp),
+            Tiling::Vivante => 4 * cpp,
+        }
+    }
+
+    /// Height of one tile in rows.
+    fn tile_height(&self, cpp: u32) -> u32 {
+        match self {
+            Tiling::IntelX => 8,
+            Tiling::IntelY => 32,
+            Tiling::Vc4T => 8 * vc4_utile_height(cpp),
+            Tiling::Vivante => 4,
+        }
+    }
+
+    /// Number of rows the buffer occupies once `height` is padded to whole tiles.
+    pub fn padded_height(&self, height: u32, cpp: u32) -> u32 {
+        height.next_multiple_of(self.tile_height(cpp))
+    }
+
+    /// Bytes that stay contiguous along a row, starting at a multiple of this value.
+    fn span(&self, cpp: u32) -> usize {
+        match self {
+            Tiling::IntelX => 512,
+            Tiling::IntelY => 16,
+            Tiling::Vc4T => vc4_utile_width(cpp) as usize,
+            Tiling::Vivante => 4 * cpp as usize,
+        }
+    }
+
+    /// Offset in the tiled buffer of byte `x` in row `y`.
+    fn offset(&self, x: usize, y: usize, pitch: usize, cpp: u32) -> usize {
+        match self {
+            Tiling::IntelX => (y / 8) * pitch * 8 + (x / 512) * 4096 + (y % 8) * 512 + x % 512,
+            Tiling::IntelY => {
+                (y / 32) * pitch * 32
+                    + (x / 128) * 4096
+                    + (x % 128 / 16) * 512
+                    + (y % 32) * 16
+                    + x % 16
+            }
+            Tiling::Vc4T => vc4_t_offset(x, y, pitch, cpp),
+            Tiling::Vivante => {
+                let tile = 4 * cpp as usize;
+                (y / 4) * pitch * 4 + (x / tile) * tile * 4 + (y % 4) * tile + x % tile
+            }
+        }
+    }
+}
+
+// VC4 micro-tiles are always 64 bytes: 8x8 pixels at 1 byte per pixel, else 4 rows.
+fn vc4_utile_height(cpp: u32) -> u32 {
+    if cpp == 1 { 8 } else { 4 }
+}
+
+fn vc4_utile_width(cpp: u32) -> u32 {
+    64 / vc4_utile_height(cpp)
+}
+
+fn vc4_t_offset(x: usize, y: usize, pitch: usize, cpp: u32) -> usize {
+    // Subtile order within a 4 KiB tile, indexed by (subtile_y * 2 + subtile_x)
+    const EVEN_ROW_SUBTILES: [usize; 4] = [0, 3, 1, 2];
+    const ODD_ROW_SUBTILES: [usize; 4] = [2, 1, 3, 0];
+
+    let utile_w = vc4_utile_width(cpp) as usize;
+    let utile_h = vc4_utile_height(cpp) as usize;
+    let (utile_x, utile_y) = (x / utile_w, y / utile_h);
+
+    let tiles_per_row = pitch / (8 * utile_w);
+    let (tile_x, tile_y) = (utile_x / 8, utile_y / 8);
+    let odd_row = tile_y % 2 == 1;
+    let tile_index = if odd_row {
+        tile_y * tiles_per_row + (tiles_per_row - 1 - tile_x)
+    } else {
+        tile_y * tiles_per_row + tile_x
+    };
+
+    let subtile = (utile_y / 4 % 2) * 2 + utile_x / 4 % 2;
+    let subtile_index = if odd_row {
+        ODD_ROW_SUBTILES[subtile]
+    } else {
+        EVEN_ROW_SUBTILES[subtile]
+    };
+    let utile_index = (utile_y % 4) * 4 + utile_x % 4;
+
+    tile_index * 4096
+        + subtile_index * 1024
+        + utile_index * 64
+        + (y % utile_h) * utile_w
+        + x % utile_w
+}
+
+/// Rearrange a tiled buffer into linear rows of `pitch` bytes so it can be passed
+/// to `pixel_format::convert_to_rgba`. `src` must hold the whole padded buffer,
+/// `pitch * tiling.padded_height(height, cpp)` bytes.
+pub fn detile(
+    src: &[u8],
+    width: u32,
+    height: u32,
+    pitch: u32,
+    cpp: u32,
+    tiling: Tiling,
+) -> Result<Vec<u8>, String> {
+    let tile_width = tiling.tile_width(cpp);
+    if pitch == 0 || !pitch.is_multiple_of(tile_width) {
+        return Err(format!(
+            "Pitch {pitch} is not a multiple of the {tiling:?} tile width ({tile_width} bytes)"
+        ));
+    }
+    let row_bytes = (width * cpp) as usize;
+    if row_bytes > pitch as usize {
+        return Err(format!("Row of {row_bytes} bytes does not fit in pitch {pitch}"));
+    }
+    let needed = pitch as usize * tiling.padded_height(height, cpp) as usize;
+    if src.len() < needed {
+        return Err(format!(
+            "Tiled buffer is {} bytes, expected at least {needed}",
+            src.len()
+        ));
+    }
+
+    let pitch = pitch as usize;
+    let span = tiling.span(cpp);
+    let mut dst = vec![0u8; pitch * height as usize];
+    for y in 0..height as usize {
+        let row = &mut dst[y * pitch..][..row_bytes];
+        let mut x = 0;
+        while x < row_bytes {
+            let len = (span - x % span).min(row_bytes - x);
+            let from = tiling.offset(x, y, pitch, cpp);
+            row[x..x + len].copy_from_slice(&src[from..from + len]);
+            x += len;
+        }
+    }
+    Ok(dst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A linear buffer with a distinct-looking byte everywhere.
+    fn pattern(pitch: usize, rows: usize) -> Vec<u8> {
+        (0..pitch * rows)
+            .map(|i| (i % pitch * 7 + i / pitch * 13) as u8)
+            .collect()
+    }
+
+    /// Lay `linear` out the way the GPU would, checking every offset is used once.
+    fn tile(linear: &[u8], pitch: usize, rows: usize, cpp: u32, tiling: Tiling) -> Vec<u8> {
+        let mut tiled = vec![0u8; pitch * rows];
+        let mut used = vec![false; pitch * rows];
+        for y in 0..rows {
+            for x in 0..pitch {
+                let offset = tiling.offset(x, y, pitch, cpp);
+                assert!(
+                    !used[offset],
+                    "{tiling:?} maps two bytes to offset {offset}"
+                );
+                used[offset] = true;
+                tiled[offset] = linear[y * pitch + x];
+            }
+        }
+        tiled
+    }
+
+    /// Tile a pattern of `tiles_wide` tiles and `height` rows, detile it and compare.
+    fn round_trip(tiling: Tiling, cpp: u32, tiles_wide: u32, height: u32) {
+        let pitch = tiling.tile_width(cpp) * tiles_wide;
+        let rows = tiling.padded_height(height, cpp);
+        let linear = pattern(pitch as usize, rows as usize);
+        let tiled = tile(&linear, pitch as usize, rows as usize, cpp, tiling);
+
+        // Leave part of the last tile column unused, as real buffers often do
+        let width = pitch / cpp - 1;
+        let detiled = detile(&tiled, width, height, pitch, cpp, tiling).unwrap();
+        let row_bytes = (width * cpp) as usize;
+        for y in 0..height as usize {
+            let start = y * pitch as usize;
+            assert_eq!(
+                detiled[start..start + row_bytes],
+                linear[start..start + row_bytes],
+                "{tiling:?} cpp {cpp}: row {y} differs"
+            );
+        }
+    }
+
+    #[test]
+    fn intel_x_round_trips() {
+        round_trip(Tiling::IntelX, 4, 2, 20);
+    }
+
+    #[test]
+    fn intel_y_round_trips() {
+        round_trip(Tiling::IntelY, 4, 2, 40);
+    }
+
+    #[test]
+    fn intel_x_offsets_match_the_spec() {
+        // 512-byte rows of 8-row tiles, tiles laid out left to right
+        let pitch = 2 * 512;
+        assert_eq!(Tiling::IntelX.offset(0, 1, pitch, 4), 512);
+        assert_eq!(Tiling::IntelX.offset(511, 7, pitch, 4), 4095);
+        assert_eq!(Tiling::IntelX.offset(512, 0, pitch, 4), 4096);
+        assert_eq!(Tiling::IntelX.offset(0, 8, pitch, 4), 2 * 4096);
+    }
+
+    #[test]
+    fn intel_y_offsets_match_the_spec() {
+        // 16-byte OWords stacked 32 rows high, then the next column of OWords
+        let pitch = 2 * 128;
+        assert_eq!(Tiling::IntelY.offset(0, 1, pitch, 4), 16);
+        assert_eq!(Tiling::IntelY.offset(16, 0, pitch, 4), 512);
+        assert_eq!(Tiling::IntelY.offset(127, 31, pitch, 4), 4095);
+        assert_eq!(Tiling::IntelY.offset(128, 0, pitch, 4), 4096);
+        assert_eq!(Tiling::IntelY.offset(0, 32, pitch, 4), 2 * 4096);
+    }
+
+    #[test]
+    fn vivante_offsets_match_the_spec() {
+        // 4x4-pixel tiles of 64 bytes at 4 bytes per pixel, row-major
+        let pitch = 4 * 16;
+        assert_eq!(Tiling::Vivante.offset(4, 0, pitch, 4), 4);
+        assert_eq!(Tiling::Vivante.offset(0, 1, pitch, 4), 16);
+        assert_eq!(Tiling::Vivante.offset(16, 0, pitch, 4), 64);
+        assert_eq!(Tiling::Vivante.offset(0, 4, pitch, 4), 4 * 64);
+    }
+
+    #[test]
+    fn vc4_t_round_trips_even_and_odd_tile_rows() {
+        for cpp in [1, 2, 4] {
+            let tile_height = Tiling::Vc4T.tile_height(cpp);
+            // Three tile rows: even, odd (right to left) and a partial even one
+            round_trip(Tiling::Vc4T, cpp, 3, tile_height * 2 + 5);
+        }
+    }
+
+    #[test]
+    fn vc4_t_odd_tile_rows_run_right_to_left() {
+        // cpp 4: tiles are 128 bytes x 32 rows; three tiles per row
+        let pitch = 3 * 128;
+        assert_eq!(Tiling::Vc4T.offset(0, 0, pitch, 4), 0);
+        // First column of the second tile row is the last tile of that row,
+        // starting with subtile 2
+        assert_eq!(Tiling::Vc4T.offset(0, 32, pitch, 4), 5 * 4096 + 2 * 1024);
+    }
+
+    #[test]
+    fn vivante_round_trips() {
+        round_trip(Tiling::Vivante, 4, 4, 10);
+        round_trip(Tiling::Vivante, 2, 3, 7);
+    }
+
+    #[test]
+    fn detile_rejects_a_pitch_that_is_not_whole_tiles() {
+        let src = vec![0u8; 500 * 8];
+        let err = detile(&src, 100, 8, 500, 4, Tiling::IntelX).unwrap_err();
+        assert!(err.contains("not a multiple"), "{err}");
+    }
+
+    #[test]
+    fn detile_rejects_a_short_buffer() {
+        // 20 rows are padded to 24, so 20 rows' worth of bytes is too short
+        let src = vec![0u8; 512 * 20];
+        let err = detile(&src, 128, 20, 512, 4, Tiling::IntelX).unwrap_err();
+        assert!(err.contains("expected at least"), "{err}");
+    }
+}
@@ -1,7 +1,9 @@
 use image::RgbaImage;
 use rmcp::ErrorData as McpError;
 
-use super::{MonitorInfo, WindowInfo};
+use regex::Regex;
+
+use super::{MonitorInfo, WindowInfo, WindowSelector};
 
 pub struct XcapBackend;
 
@@ -32,42 +34,84 @@ impl XcapBackend {
             .map_err(|e| McpError::internal_error(format!("Failed to capture screen: {e}"), None))
     }
 
-    pub fn capture_window(&self, window_id: u32) -> Result<RgbaImage, McpError> {
+    pub fn capture_window(
+        &self,
+        selector: &WindowSelector,
+    ) -> Result<(WindowInfo, RgbaImage), McpError> {
+        let title_regex = selector
+            .title_regex
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| McpError::invalid_params(format!("Invalid title_regex: {e}"), None))?;
+
         let windows = xcap::Window::all()
             .map_err(|e| McpError::internal_error(format!("Failed to list windows: {e}"), None))?;
-        let window = windows
+        let mut matches: Vec<(xcap::Window, WindowInfo)> = windows
             .into_iter()
-            .find(|w| w.id().ok() == Some(window_id))
-            .ok_or_else(|| {
-                McpError::invalid_params(
-                    format!("Window with ID {window_id} not found"),
+            .filter_map(|w| {
+                let info = Self::window_info(&w)?;
+                let matched = selector.id.is_none_or(|id| info.id == id)
+                    && selector.title.as_ref().is_none_or(|t| &info.title == t)
+                    && title_regex.as_ref().is_none_or(|re| re.is_match(&info.title))
+                    && selector
+                        .app_name
+                        .as_ref()
+                        .is_none_or(|a| info.app_name.eq_ignore_ascii_case(a))
+                    && selector.pid.is_none_or(|pid| w.pid().ok() == Some(pid))
+                    && (!selector.focused || w.is_focused().unwrap_or(false));
+                matched.then_some((w, info))
+            })
+            .collect();
+
+        let (window, info) = match matches.len() {
+            0 => {
+                return Err(McpError::invalid_params(
+                    format!("No window matches {selector}"),
+                    None,
+                ));
+            }
+            1 => matches.remove(0),
+            n => {
+                let candidates: Vec<String> = matches
+                    .iter()
+                    .map(|(_, i)| format!("{} ({:?}, {})", i.id, i.title, i.app_name))
+                    .collect();
+                return Err(McpError::invalid_params(
+                    format!(
+                        "{n} windows match {selector}; narrow the selector or use window_id. \
+                         Candidates: {}",
+                        candidates.join(", ")
+                    ),
                     None,
-                )
-            })?;
-        window
+                ));
+            }
+        };
+
+        let image = window
             .capture_image()
-            .map_err(|e| McpError::internal_error(format!("Failed to capture window: {e}"), None))
+            .map_err(|e| McpError::internal_error(format!("Failed to capture window: {e}"), None))?;
+        Ok((info, image))
+    }
+
+    fn window_info(w: &xcap::Window) -> Option<WindowInfo> {
+        Some(WindowInfo {
+            id: w.id().ok()?,
+            title: w.title().unwrap_or_default(),
+            app_name: w.app_name().unwrap_or_default(),
+            x: w.x().unwrap_or(0),
+            y: w.y().unwrap_or(0),
+            width: w.width().unwrap_or(0),
+            height: w.height().unwrap_or(0),
+            is_minimized: w.is_minimized().unwrap_or(false),
+            is_maximized: w.is_maximized().unwrap_or(false),
+        })
     }
 
     pub fn list_windows(&self) -> Result<Vec<WindowInfo>, McpError> {
         let windows = xcap::Window::all()
             .map_err(|e| McpError::internal_error(format!("Failed to list windows: {e}"), None))?;
-        Ok(windows
-            .iter()
-            .filter_map(|w| {
-                Some(WindowInfo {
-                    id: w.id().ok()?,
-                    title: w.title().unwrap_or_default(),
-                    app_name: w.app_name().unwrap_or_default(),
-                    x: w.x().unwrap_or(0),
-                    y: w.y().unwrap_or(0),
-                    width: w.width().unwrap_or(0),
-                    height: w.height().unwrap_or(0),
-                    is_minimized: w.is_minimized().unwrap_or(false),
-                    is_maximized: w.is_maximized().unwrap_or(false),
-                })
-            })
-            .collect())
+        Ok(windows.iter().filter_map(Self::window_info).collect())
     }
 
     pub fn list_monitors(&self) -> Result<Vec<MonitorInfo>, McpError> {
@@ -84,6 +128,10 @@ impl XcapBackend {
                     width: m.width().unwrap_or(0),
                     height: m.height().unwrap_or(0),
                     is_primary: m.is_primary().unwrap_or(false),
+                    refresh_rate: m.frequency().ok().filter(|&hz| hz > 0.0),
+                    rotation: m.rotation().ok().map(|deg| deg.rem_euclid(360.0).round() as u32),
+                    scale_factor: m.scale_factor().ok(),
+                    ..Default::default()
                 })
             })
             .collect())
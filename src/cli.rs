@@ -0,0 +1,333 @@
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use image::DynamicImage;
+use rmcp::serde_json;
+use serde::Serialize;
+
+use crate::backend::{Backend, WindowSelector, capture_scale};
+use crate::encode::{self, OutputFormat};
+use crate::server::ServerConfig;
+
+#[derive(Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// KMS connector to use as the primary monitor, e.g. `HDMI-A-1` or `card1-HDMI-A-1`
+    #[arg(long, global = true, env = "MCP_SCREENSHOT_PRIMARY_OUTPUT")]
+    pub primary_output: Option<String>,
+
+    /// Options for the default `serve` command
+    #[command(flatten)]
+    pub serve: ServeArgs,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run the MCP server (default)
+    Serve(ServeArgs),
+    /// Take one screenshot and write it to stdout or a file
+    Capture(CaptureArgs),
+    /// Print monitors as JSON
+    ListMonitors(ListArgs),
+    /// Print windows as JSON
+    ListWindows(ListArgs),
+    /// Report every step of backend detection to diagnose capture problems
+    Doctor(DoctorArgs),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TransportKind {
+    Stdio,
+    Http,
+}
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// MCP transport
+    #[arg(long, value_enum, env = "MCP_SCREENSHOT_TRANSPORT", default_value = "stdio")]
+    pub transport: TransportKind,
+
+    /// Shorthand for `--transport http`
+    #[arg(long)]
+    pub http: bool,
+
+    #[cfg(feature = "http")]
+    #[command(flatten)]
+    pub http_args: HttpArgs,
+
+    /// Timeout for a single capture or listing, in milliseconds
+    #[arg(long, env = "MCP_SCREENSHOT_CAPTURE_TIMEOUT_MS", default_value_t = 10_000,
+          value_parser = clap::value_parser!(u64).range(1..))]
+    pub capture_timeout_ms: u64,
+
+    /// Poll interval for subscribed resources, in milliseconds
+    #[arg(long, env = "MCP_SCREENSHOT_WATCH_INTERVAL_MS", default_value_t = 1_000,
+          value_parser = clap::value_parser!(u64).range(1..))]
+    pub watch_interval_ms: u64,
+
+    /// Fraction of changed pixels that marks a subscribed resource as updated
+    #[arg(long, env = "MCP_SCREENSHOT_WATCH_THRESHOLD", default_value_t = 0.001,
+          value_parser = parse_fraction)]
+    pub watch_threshold: f64,
+
+    /// How often to re-probe KMS outputs for hotplugs, in milliseconds (0 disables)
+    #[arg(long, env = "MCP_SCREENSHOT_HOTPLUG_INTERVAL_MS", default_value_t = 2_000)]
+    pub hotplug_interval_ms: u64,
+
+    /// How long a monitor capture is reused by later requests, in milliseconds (0 disables)
+    #[arg(long, env = "MCP_SCREENSHOT_FRAME_CACHE_MS", default_value_t = 0)]
+    pub frame_cache_ms: u64,
+}
+
+impl ServeArgs {
+    pub fn use_http(&self) -> bool {
+        self.http || self.transport == TransportKind::Http
+    }
+
+    pub fn server_config(&self) -> ServerConfig {
+        ServerConfig {
+            capture_timeout: Duration::from_millis(self.capture_timeout_ms),
+            watch_interval: Duration::from_millis(self.watch_interval_ms),
+            watch_threshold: self.watch_threshold,
+            hotplug_interval: Duration::from_millis(self.hotplug_interval_ms),
+        }
+    }
+
+    pub fn frame_cache_ttl(&self) -> Duration {
+        Duration::from_millis(self.frame_cache_ms)
+    }
+}
+
+#[cfg(feature = "http")]
+#[derive(Args)]
+pub struct HttpArgs {
+    /// HTTP port
+    #[arg(long, env = "MCP_SCREENSHOT_PORT", default_value_t = 8080)]
+    pub port: u16,
+
+    /// Address or host name to bind
+    #[arg(long, env = "MCP_SCREENSHOT_BIND", default_value = "0.0.0.0")]
+    pub bind: String,
+
+    /// Listen on a Unix domain socket instead of TCP
+    #[cfg(unix)]
+    #[arg(long, env = "MCP_SCREENSHOT_UNIX_SOCKET")]
+    pub unix_socket: Option<PathBuf>,
+
+    /// Bearer token required on every HTTP request
+    #[arg(long, env = "MCP_SCREENSHOT_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+
+    /// File containing the bearer token
+    #[arg(long, env = "MCP_SCREENSHOT_TOKEN_FILE", conflicts_with = "token")]
+    pub token_file: Option<PathBuf>,
+
+    /// PEM certificate chain for HTTPS
+    #[cfg(feature = "tls")]
+    #[arg(long, env = "MCP_SCREENSHOT_TLS_CERT", requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key for HTTPS
+    #[cfg(feature = "tls")]
+    #[arg(long, env = "MCP_SCREENSHOT_TLS_KEY", requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+}
+
+/// Parse a fraction from 0.0 to 1.0.
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    let value: f64 = s.parse().map_err(|e| format!("invalid number '{s}': {e}"))?;
+    if (0.0..=1.0).contains(&value) {
+        Ok(value)
+    } else {
+        Err(format!("{s} is not between 0 and 1"))
+    }
+}
+
+/// Rectangle given as `X,Y,WIDTH,HEIGHT`.
+#[derive(Clone, Copy)]
+pub struct RegionArg {
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+}
+
+impl FromStr for RegionArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(',').map(str::trim).collect();
+        let [x, y, width, height] = parts[..] else {
+            return Err("expected X,Y,WIDTH,HEIGHT".to_string());
+        };
+        let err = |e: std::num::ParseIntError| format!("invalid region '{s}': {e}");
+        Ok(RegionArg {
+            x: x.parse().map_err(err)?,
+            y: y.parse().map_err(err)?,
+            width: width.parse().map_err(err)?,
+            height: height.parse().map_err(err)?,
+        })
+    }
+}
+
+#[derive(Args)]
+#[command(group(
+    ArgGroup::new("window")
+        .multiple(true)
+        .args(["window_id", "title", "title_regex", "app_name", "pid", "focused"])
+        .conflicts_with_all(["monitor", "all", "region"])
+))]
+pub struct CaptureArgs {
+    /// Monitor ID (default: primary monitor)
+    #[arg(long, conflicts_with = "all")]
+    monitor: Option<u32>,
+
+    /// Capture all monitors as one virtual-desktop image
+    #[arg(long)]
+    all: bool,
+
+    /// Capture only this rectangle, relative to the monitor unless --global is set
+    #[arg(long, value_name = "X,Y,WIDTH,HEIGHT", conflicts_with = "all")]
+    region: Option<RegionArg>,
+
+    /// Interpret --region in global desktop coordinates
+    #[arg(long, requires = "region")]
+    global: bool,
+
+    /// Window ID to capture
+    #[arg(long)]
+    window_id: Option<u32>,
+
+    /// Exact window title
+    #[arg(long)]
+    title: Option<String>,
+
+    /// Regular expression matched against the window title
+    #[arg(long)]
+    title_regex: Option<String>,
+
+    /// Application name (case-insensitive)
+    #[arg(long)]
+    app_name: Option<String>,
+
+    /// Process ID owning the window
+    #[arg(long)]
+    pid: Option<u32>,
+
+    /// Capture the focused window
+    #[arg(long)]
+    focused: bool,
+
+    /// Image format (default: from the output file extension, else png)
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// JPEG quality from 1 to 100 (JPEG only; the other formats are lossless)
+    #[arg(long)]
+    quality: Option<u8>,
+
+    /// Downscale to at most this width
+    #[arg(long)]
+    max_width: Option<u32>,
+
+    /// Downscale to at most this height
+    #[arg(long)]
+    max_height: Option<u32>,
+
+    /// Output file (default: stdout)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+impl CaptureArgs {
+    fn window_selector(&self) -> WindowSelector {
+        WindowSelector {
+            id: self.window_id,
+            title: self.title.clone(),
+            title_regex: self.title_regex.clone(),
+            app_name: self.app_name.clone(),
+            pid: self.pid,
+            focused: self.focused,
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct ListArgs {
+    /// Output file (default: stdout)
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+pub struct DoctorArgs {
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+pub fn run_capture(backend: &Backend, args: &CaptureArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let selector = args.window_selector();
+    let (img, origin, scale) = if !selector.is_empty() {
+        let (window, rgba) = backend.capture_window(&selector)?;
+        let scale = capture_scale(window.width, rgba.width());
+        (DynamicImage::ImageRgba8(rgba), (window.x, window.y), scale)
+    } else if let Some(r) = args.region {
+        let region = if args.global {
+            backend.capture_desktop_region(r.x, r.y, r.width, r.height, false)?
+        } else {
+            backend.capture_region(args.monitor, r.x, r.y, r.width, r.height, false)?
+        };
+        for rect in &region.off_screen {
+            eprintln!(
+                "warning: {}x{} at ({}, {}) is off-screen and was not captured",
+                rect.width, rect.height, rect.x, rect.y
+            );
+        }
+        (region.image, region.origin, region.scale)
+    } else if args.all {
+        let (rgba, origin, scale) = backend.capture_all(false)?;
+        (DynamicImage::ImageRgba8(rgba), origin, scale)
+    } else {
+        let monitor = backend.monitor_info(args.monitor)?;
+        let rgba = backend.capture_monitor(Some(monitor.id), false)?;
+        let scale = capture_scale(monitor.width, rgba.width());
+        (DynamicImage::ImageRgba8(rgba), (monitor.x, monitor.y), scale)
+    };
+
+    let (img, _) = encode::fit_within(img, origin, scale, args.max_width, args.max_height)?;
+    let output = args.output.as_ref().map(|p| p.to_string_lossy().into_owned());
+    let format = OutputFormat::resolve(args.format, output.as_deref())?;
+    let encoded = encode::encode(&img, format, args.quality)?;
+
+    match output {
+        Some(path) => {
+            let path = encoded.save(&path)?;
+            eprintln!("Screenshot saved to {path}");
+        }
+        None => {
+            let mut stdout = std::io::stdout().lock();
+            if stdout.is_terminal() {
+                return Err("Refusing to write binary image data to a terminal; use --output or redirect stdout".into());
+            }
+            stdout.write_all(&encoded.bytes)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(())
+}
+
+pub fn run_list<T: Serialize>(items: &T, args: &ListArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let json = serde_json::to_string_pretty(items)?;
+    match &args.output {
+        Some(path) => std::fs::write(path, json + "\n")?,
+        None => println!("{json}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use image::RgbaImage;
+
+use crate::backend::Rect;
+
+/// Per-channel difference below which two pixels count as equal, so that
+/// dithering and compression noise do not register as changes.
+const CHANNEL_TOLERANCE: u8 = 8;
+
+/// How much two frames differ.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameDiff {
+    pub changed_pixels: u64,
+    pub total_pixels: u64,
+    /// Smallest rectangle (in the second frame's pixels) holding every changed pixel.
+    pub bounds: Option<Rect>,
+}
+
+impl FrameDiff {
+    /// Fraction of pixels that changed, from 0.0 to 1.0.
+    pub fn fraction(&self) -> f64 {
+        if self.total_pixels == 0 {
+            0.0
+        } else {
+            self.changed_pixels as f64 / self.total_pixels as f64
+        }
+    }
+
+    /// Every pixel of `frame` changed.
+    pub fn whole(frame: &RgbaImage) -> Self {
+        let total_pixels = (frame.width() as u64 * frame.height() as u64).max(1);
+        FrameDiff {
+            changed_pixels: total_pixels,
+            total_pixels,
+            bounds: (frame.width() > 0 && frame.height() > 0).then(|| Rect {
+                x: 0,
+                y: 0,
+                width: frame.width(),
+                height: frame.height(),
+            }),
+        }
+    }
+}
+
+/// Compare two frames pixel by pixel. Frames of different sizes count as fully changed.
+pub fn compare(a: &RgbaImage, b: &RgbaImage) -> FrameDiff {
+    if a.dimensions() != b.dimensions() {
+        return FrameDiff::whole(b);
+    }
+    let total_pixels = a.width() as u64 * a.height() as u64;
+
+    let mut changed_pixels = 0;
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for ((x, y, pa), pb) in a.enumerate_pixels().zip(b.pixels()) {
+        let changed =
+            pa.0.iter()
+                .zip(pb.0.iter())
+                .any(|(ca, cb)| ca.abs_diff(*cb) > CHANNEL_TOLERANCE);
+        if changed {
+            changed_pixels += 1;
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+
+    FrameDiff {
+        changed_pixels,
+        total_pixels,
+        bounds: (changed_pixels > 0).then(|| Rect {
+            x: min_x as i32,
+            y: min_y as i32,
+            width: max_x - min_x + 1,
+            height: max_y - min_y + 1,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::Rgba;
+
+    fn frame(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, Rgba([40, 80, 120, 255]))
+    }
+
+    #[test]
+    fn unchanged_frame_has_no_diff() {
+        let diff = compare(&frame(8, 6), &frame(8, 6));
+        assert_eq!(diff.changed_pixels, 0);
+        assert_eq!(diff.total_pixels, 48);
+        assert_eq!(diff.fraction(), 0.0);
+        assert_eq!(diff.bounds, None);
+    }
+
+    #[test]
+    fn single_pixel_changes_at_the_edges_are_bounded() {
+        let a = frame(8, 6);
+        for (x, y) in [(0, 0), (7, 0), (0, 5), (7, 5)] {
+            let mut b = a.clone();
+            b.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            let diff = compare(&a, &b);
+            assert_eq!(diff.changed_pixels, 1);
+            assert_eq!(diff.fraction(), 1.0 / 48.0);
+            assert_eq!(
+                diff.bounds,
+                Some(Rect {
+                    x: x as i32,
+                    y: y as i32,
+                    width: 1,
+                    height: 1
+                })
+            );
+        }
+
+        let mut b = a.clone();
+        b.put_pixel(0, 0, Rgba([0, 0, 0, 255]));
+        b.put_pixel(7, 5, Rgba([0, 0, 0, 255]));
+        assert_eq!(
+            compare(&a, &b).bounds,
+            Some(Rect {
+                x: 0,
+                y: 0,
+                width: 8,
+                height: 6
+            })
+        );
+    }
+
+    #[test]
+    fn changes_within_the_tolerance_are_ignored() {
+        let a = frame(4, 4);
+        let mut b = a.clone();
+        b.put_pixel(
+            1,
+            1,
+            Rgba([40 + CHANNEL_TOLERANCE, 80 - CHANNEL_TOLERANCE, 120, 255]),
+        );
+        assert_eq!(compare(&a, &b).changed_pixels, 0);
+
+        b.put_pixel(2, 2, Rgba([40, 80, 120 + CHANNEL_TOLERANCE + 1, 255]));
+        let diff = compare(&a, &b);
+        assert_eq!(diff.changed_pixels, 1);
+        assert_eq!(
+            diff.bounds,
+            Some(Rect {
+                x: 2,
+                y: 2,
+                width: 1,
+                height: 1
+            })
+        );
+    }
+
+    #[test]
+    fn size_mismatch_is_a_full_change() {
+        let diff = compare(&frame(8, 6), &frame(4, 3));
+        assert_eq!(diff.fraction(), 1.0);
+        assert_eq!(
+            diff.bounds,
+            Some(Rect {
+                x: 0,
+                y: 0,
+                width: 4,
+                height: 3
+            })
+        );
+
+        let diff = compare(&frame(8, 6), &frame(0, 0));
+        assert_eq!(diff.fraction(), 1.0);
+        assert_eq!(diff.bounds, None);
+    }
+}
@@ -0,0 +1,268 @@
+use rmcp::serde_json;
+use serde::Serialize;
+
+use crate::backend;
+#[cfg(feature = "kms")]
+use crate::backend::{KmsBackend, KmsDiagnostics, ProbeCheck};
+#[cfg(feature = "fbdev")]
+use crate::backend::{FbdevBackend, FbdevDiagnostics};
+
+/// Environment variables that influence backend detection.
+const ENV_VARS: &[&str] = &[
+    "MCP_SCREENSHOT_BACKEND",
+    "MCP_SCREENSHOT_PRIMARY_OUTPUT",
+    "DISPLAY",
+    "WAYLAND_DISPLAY",
+    "XDG_SESSION_TYPE",
+    "XDG_RUNTIME_DIR",
+];
+
+/// Capability bit number of CAP_SYS_ADMIN (see capabilities(7)).
+const CAP_SYS_ADMIN: u32 = 21;
+
+#[derive(Serialize)]
+pub struct Report {
+    /// Cargo features this binary was built with.
+    pub features: Vec<&'static str>,
+    pub env: Vec<EnvVar>,
+    /// Whether CAP_SYS_ADMIN is in the effective set; `None` if it cannot be read.
+    pub cap_sys_admin: Option<bool>,
+    #[cfg(feature = "kms")]
+    pub kms: KmsDiagnostics,
+    #[cfg(feature = "fbdev")]
+    pub fbdev: Vec<FbdevDiagnostics>,
+    pub backend: BackendCheck,
+}
+
+#[derive(Serialize)]
+pub struct EnvVar {
+    pub name: &'static str,
+    pub value: Option<String>,
+}
+
+/// The backend `backend::detect` picks, and whether it can list monitors.
+#[derive(Serialize)]
+pub struct BackendCheck {
+    pub selected: Option<&'static str>,
+    pub monitors: Option<usize>,
+    pub error: Option<String>,
+}
+
+impl Report {
+    pub fn collect(primary_output: Option<&str>) -> Self {
+        Report {
+            features: enabled_features(),
+            env: ENV_VARS
+                .iter()
+                .map(|&name| EnvVar {
+                    name,
+                    value: std::env::var(name).ok(),
+                })
+                .collect(),
+            cap_sys_admin: has_cap_sys_admin(),
+            #[cfg(feature = "kms")]
+            kms: KmsBackend::diagnose(),
+            #[cfg(feature = "fbdev")]
+            fbdev: FbdevBackend::diagnose(),
+            backend: check_backend(primary_output),
+        }
+    }
+
+    /// Whether a backend was found and could list at least one monitor.
+    pub fn is_healthy(&self) -> bool {
+        self.backend.monitors.is_some_and(|n| n > 0)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let mut line = |s: String| {
+            out.push_str(&s);
+            out.push('\n');
+        };
+
+        line(format!("Features: {}", self.features.join(", ")));
+        line("Environment:".to_string());
+        for var in &self.env {
+            line(format!(
+                "  {:<24}{}",
+                var.name,
+                var.value.as_deref().unwrap_or("(unset)")
+            ));
+        }
+        line(format!(
+            "CAP_SYS_ADMIN: {}",
+            match self.cap_sys_admin {
+                Some(true) => "yes",
+                Some(false) => "no (KMS capture needs it: sudo setcap cap_sys_admin+ep <binary>)",
+                None => "unknown",
+            }
+        ));
+
+        #[cfg(feature = "kms")]
+        {
+            line("KMS:".to_string());
+            if let Some(e) = &self.kms.dri_error {
+                line(format!("  {e}"));
+            } else if self.kms.cards.is_empty() {
+                line("  no /dev/dri/card* devices".to_string());
+            }
+            for card in &self.kms.cards {
+                match &card.driver {
+                    Some(driver) => line(format!("  {} ({driver})", card.path)),
+                    None => line(format!("  {}", card.path)),
+                }
+                if let Some(e) = &card.error {
+                    line(format!("    {e}"));
+                }
+                for conn in &card.connectors {
+                    let mut desc = format!("    {}: {}", conn.name, conn.state);
+                    if let Some(crtc) = conn.crtc {
+                        desc += &format!(", CRTC {crtc}");
+                    }
+                    if let Some(mode) = &conn.mode {
+                        desc += &format!(", {mode}");
+                    }
+                    if conn.state == "connected" && !conn.active {
+                        desc += ", not active";
+                    }
+                    if let Some(id) = conn.monitor_id {
+                        desc += &format!(", monitor {id}");
+                    }
+                    line(desc);
+
+                    let Some(fb) = &conn.framebuffer else {
+                        continue;
+                    };
+                    let check = |c: &ProbeCheck| match &c.error {
+                        None => "ok".to_string(),
+                        Some(e) => format!("failed ({e})"),
+                    };
+                    line(format!("      framebuffer {}", fb.id));
+                    line(format!("        GET_FB2: {}", check(&fb.get_fb2)));
+                    if let Some(format) = &fb.pixel_format {
+                        let supported = if fb.format_supported == Some(true) {
+                            "supported"
+                        } else {
+                            "unsupported"
+                        };
+                        line(format!("        pixel format: {format} ({supported})"));
+                    }
+                    if let Some(modifier) = &fb.modifier {
+                        line(format!("        modifier: {modifier}"));
+                    }
+                    line(format!("        GET_FB: {}", check(&fb.get_fb)));
+                    line(format!(
+                        "        buffer handle: {}",
+                        if fb.buffer_handle {
+                            "yes"
+                        } else {
+                            "no (withheld without CAP_SYS_ADMIN)"
+                        }
+                    ));
+                    line(format!("        capture: {}", check(&fb.capture)));
+                    for plane in &conn.planes {
+                        let mut desc = format!(
+                            "      {} plane {}: framebuffer {}",
+                            plane.kind, plane.id, plane.framebuffer
+                        );
+                        if let Some(dst) = &plane.dst {
+                            desc +=
+                                &format!(" at {},{} {}x{}", dst.x, dst.y, dst.width, dst.height);
+                        }
+                        if let Some(zpos) = plane.zpos {
+                            desc += &format!(", zpos {zpos}");
+                        }
+                        line(desc);
+                    }
+                }
+            }
+        }
+
+        #[cfg(feature = "fbdev")]
+        {
+            line("fbdev:".to_string());
+            if self.fbdev.is_empty() {
+                line("  no /dev/fb* devices".to_string());
+            }
+            for fb in &self.fbdev {
+                let mut desc = format!("  {}", fb.path);
+                if let Some(id) = fb.id.as_deref().filter(|id| !id.is_empty()) {
+                    desc += &format!(" ({id})");
+                }
+                if let Some(e) = &fb.error {
+                    desc += &format!(": cannot open: {e}");
+                }
+                if let Some(resolution) = &fb.resolution {
+                    desc += &format!(": {resolution}");
+                }
+                match (&fb.layout, &fb.layout_error) {
+                    (Some(layout), _) => desc += &format!(", {layout}"),
+                    (None, Some(e)) => desc += &format!(", unsupported: {e}"),
+                    (None, None) => {}
+                }
+                line(desc);
+            }
+        }
+
+        match (&self.backend.selected, &self.backend.error) {
+            (Some(name), None) => line(format!(
+                "Backend: {name} ({} monitor(s))",
+                self.backend.monitors.unwrap_or(0)
+            )),
+            (Some(name), Some(e)) => line(format!("Backend: {name}, but listing monitors failed: {e}")),
+            (None, Some(e)) => line(format!("Backend: none ({e})")),
+            (None, None) => line("Backend: none".to_string()),
+        }
+        out
+    }
+}
+
+fn enabled_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "desktop") {
+        features.push("desktop");
+    }
+    if cfg!(feature = "kms") {
+        features.push("kms");
+    }
+    if cfg!(feature = "fbdev") {
+        features.push("fbdev");
+    }
+    if cfg!(feature = "http") {
+        features.push("http");
+    }
+    if cfg!(feature = "tls") {
+        features.push("tls");
+    }
+    features
+}
+
+/// Read the effective capability set from `/proc/self/status`.
+fn has_cap_sys_admin() -> Option<bool> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let hex = status.lines().find_map(|l| l.strip_prefix("CapEff:"))?.trim();
+    let caps = u64::from_str_radix(hex, 16).ok()?;
+    Some(caps & (1 << CAP_SYS_ADMIN) != 0)
+}
+
+fn check_backend(primary_output: Option<&str>) -> BackendCheck {
+    match backend::detect(primary_output) {
+        Ok(backend) => {
+            let monitors = backend.list_monitors();
+            BackendCheck {
+                selected: Some(backend.name()),
+                monitors: monitors.as_ref().ok().map(Vec::len),
+                error: monitors.err().map(|e| e.message.into_owned()),
+            }
+        }
+        Err(e) => BackendCheck {
+            selected: None,
+            monitors: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
@@ -0,0 +1,296 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::qoi::QoiEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::DynamicImage;
+use image::imageops::FilterType;
+use rmcp::ErrorData as McpError;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_JPEG_QUALITY: u8 = 80;
+
+/// Encoding used for returned and saved screenshots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, rmcp::schemars::JsonSchema, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    Webp,
+    Qoi,
+}
+
+impl OutputFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "image/png",
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Webp => "image/webp",
+            OutputFormat::Qoi => "image/qoi",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Qoi => "qoi",
+        }
+    }
+
+    fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_ascii_lowercase().as_str() {
+            "png" => Some(OutputFormat::Png),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+            "webp" => Some(OutputFormat::Webp),
+            "qoi" => Some(OutputFormat::Qoi),
+            _ => None,
+        }
+    }
+
+    /// Pick the format: explicit request first, then the save path's extension, then PNG.
+    /// A requested format that contradicts a known save path extension is an error,
+    /// so a JPEG is never written to `shot.png`.
+    pub fn resolve(requested: Option<Self>, save_path: Option<&str>) -> Result<Self, McpError> {
+        let from_path = save_path
+            .and_then(|p| Path::new(p).extension()?.to_str())
+            .and_then(Self::from_extension);
+        match (requested, from_path) {
+            (Some(requested), Some(from_path)) if requested != from_path => {
+                Err(McpError::invalid_params(
+                    format!(
+                        "format {requested:?} does not match the save path extension \
+                         (.{}); change one of them",
+                        save_path.and_then(|p| Path::new(p).extension()?.to_str()).unwrap_or("")
+                    ),
+                    None,
+                ))
+            }
+            (requested, from_path) => Ok(requested.or(from_path).unwrap_or(OutputFormat::Png)),
+        }
+    }
+}
+
+/// An encoded screenshot ready to be returned or written to disk.
+pub struct EncodedImage {
+    pub format: OutputFormat,
+    pub bytes: Vec<u8>,
+}
+
+impl EncodedImage {
+    pub fn to_base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(&self.bytes)
+    }
+
+    /// Write the encoded bytes to `path`, appending the format's extension if the path has none.
+    /// Returns the path actually written.
+    pub fn save(&self, path: &str) -> Result<String, McpError> {
+        let path = if Path::new(path).extension().is_some() {
+            path.to_string()
+        } else {
+            format!("{path}.{}", self.format.extension())
+        };
+        std::fs::write(&path, &self.bytes).map_err(|e| {
+            McpError::internal_error(format!("Failed to save image to {path}: {e}"), None)
+        })?;
+        Ok(path)
+    }
+}
+
+/// How pixels in a returned image map back to global desktop coordinates, the ones
+/// monitor and window positions are given in:
+/// `screen_x = origin_x + image_x / scale`, `screen_y = origin_y + image_y / scale`.
+/// `scale` covers both downscaling and the display scale of HiDPI monitors, whose
+/// captures have more pixels than desktop units.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct CoordinateMapping {
+    pub origin_x: i32,
+    pub origin_y: i32,
+    pub scale: f64,
+    pub width: u32,
+    pub height: u32,
+    pub original_width: u32,
+    pub original_height: u32,
+}
+
+/// Downscale `img` to fit within `max_width` x `max_height`, preserving aspect ratio.
+/// Images already within bounds are returned unchanged; nothing is ever upscaled.
+/// `origin` is the global desktop position of the image's top-left pixel and
+/// `pixel_scale` the captured pixels per desktop unit.
+pub fn fit_within(
+    img: DynamicImage,
+    origin: (i32, i32),
+    pixel_scale: f64,
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+) -> Result<(DynamicImage, CoordinateMapping), McpError> {
+    if max_width == Some(0) || max_height == Some(0) {
+        return Err(McpError::invalid_params(
+            "max_width and max_height must be greater than 0",
+            None,
+        ));
+    }
+
+    let (orig_w, orig_h) = (img.width(), img.height());
+    let bound_w = max_width.unwrap_or(orig_w).min(orig_w);
+    let bound_h = max_height.unwrap_or(orig_h).min(orig_h);
+    let img = if (bound_w, bound_h) == (orig_w, orig_h) {
+        img
+    } else {
+        img.resize(bound_w, bound_h, FilterType::Triangle)
+    };
+
+    let mapping = CoordinateMapping {
+        origin_x: origin.0,
+        origin_y: origin.1,
+        scale: if orig_w == 0 {
+            pixel_scale
+        } else {
+            pixel_scale * img.width() as f64 / orig_w as f64
+        },
+        width: img.width(),
+        height: img.height(),
+        original_width: orig_w,
+        original_height: orig_h,
+    };
+    Ok((img, mapping))
+}
+
+/// Encode `img` in `format`. `quality` (1-100) is only accepted for JPEG; the other
+/// formats are always lossless.
+pub fn encode(
+    img: &DynamicImage,
+    format: OutputFormat,
+    quality: Option<u8>,
+) -> Result<EncodedImage, McpError> {
+    if let Some(q) = quality {
+        if format != OutputFormat::Jpeg {
+            return Err(McpError::invalid_params(
+                format!("quality only applies to jpeg; {format:?} is always lossless"),
+                None,
+            ));
+        }
+        if !(1..=100).contains(&q) {
+            return Err(McpError::invalid_params(
+                format!("quality must be between 1 and 100, got {q}"),
+                None,
+            ));
+        }
+    }
+
+    let mut buf = Vec::new();
+    let result = match format {
+        OutputFormat::Png => img.write_to(&mut Cursor::new(&mut buf), image::ImageFormat::Png),
+        // JPEG has no alpha channel
+        OutputFormat::Jpeg => DynamicImage::ImageRgb8(img.to_rgb8()).write_with_encoder(
+            JpegEncoder::new_with_quality(&mut buf, quality.unwrap_or(DEFAULT_JPEG_QUALITY)),
+        ),
+        OutputFormat::Webp => img.write_with_encoder(WebPEncoder::new_lossless(&mut buf)),
+        OutputFormat::Qoi => img.write_with_encoder(QoiEncoder::new(&mut buf)),
+    };
+    result.map_err(|e| {
+        McpError::internal_error(format!("Failed to encode {format:?}: {e}"), None)
+    })?;
+
+    Ok(EncodedImage { format, bytes: buf })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::{GenericImageView, RgbaImage};
+
+    fn blank(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::new(width, height))
+    }
+
+    /// Desktop position of image pixel (`ix`, `iy`), as documented on `CoordinateMapping`.
+    fn to_screen(m: &CoordinateMapping, ix: f64, iy: f64) -> (f64, f64) {
+        (
+            m.origin_x as f64 + ix / m.scale,
+            m.origin_y as f64 + iy / m.scale,
+        )
+    }
+
+    #[test]
+    fn unscaled_capture_maps_one_to_one() {
+        let (img, mapping) = fit_within(blank(200, 100), (1920, 0), 1.0, None, None).unwrap();
+        assert_eq!(img.dimensions(), (200, 100));
+        assert_eq!(mapping.scale, 1.0);
+        assert_eq!(to_screen(&mapping, 10.0, 20.0), (1930.0, 20.0));
+    }
+
+    #[test]
+    fn downscaling_is_folded_into_the_scale() {
+        let (img, mapping) = fit_within(blank(200, 100), (-100, 50), 1.0, Some(100), None).unwrap();
+        assert_eq!(img.dimensions(), (100, 50));
+        assert_eq!(mapping.scale, 0.5);
+        assert_eq!(to_screen(&mapping, 100.0, 50.0), (100.0, 150.0));
+    }
+
+    #[test]
+    fn display_scale_is_folded_into_the_scale() {
+        // A 1440x900-point monitor at (1440, 0) captured at 2x, then halved to fit
+        let (img, mapping) =
+            fit_within(blank(2880, 1800), (1440, 0), 2.0, Some(1440), None).unwrap();
+        assert_eq!(img.dimensions(), (1440, 900));
+        assert_eq!(mapping.scale, 1.0);
+        // The bottom-right image pixel is the bottom-right of the monitor
+        assert_eq!(to_screen(&mapping, 1440.0, 900.0), (2880.0, 900.0));
+
+        // Unresized, each desktop unit covers two pixels
+        let (_, mapping) = fit_within(blank(2880, 1800), (1440, 0), 2.0, None, None).unwrap();
+        assert_eq!(mapping.scale, 2.0);
+        assert_eq!(to_screen(&mapping, 2880.0, 1800.0), (2880.0, 900.0));
+    }
+
+    #[test]
+    fn resolve_prefers_the_request_then_the_extension() {
+        assert_eq!(
+            OutputFormat::resolve(None, None).unwrap(),
+            OutputFormat::Png
+        );
+        assert_eq!(
+            OutputFormat::resolve(None, Some("a.JPEG")).unwrap(),
+            OutputFormat::Jpeg
+        );
+        assert_eq!(
+            OutputFormat::resolve(Some(OutputFormat::Webp), Some("a.webp")).unwrap(),
+            OutputFormat::Webp
+        );
+        // Unknown extensions do not conflict
+        assert_eq!(
+            OutputFormat::resolve(Some(OutputFormat::Qoi), Some("a.out")).unwrap(),
+            OutputFormat::Qoi
+        );
+    }
+
+    #[test]
+    fn resolve_rejects_a_conflicting_extension() {
+        let err = OutputFormat::resolve(Some(OutputFormat::Jpeg), Some("x.png")).unwrap_err();
+        assert!(err.message.contains("does not match"), "{}", err.message);
+    }
+
+    #[test]
+    fn quality_is_only_accepted_for_jpeg() {
+        let img = blank(4, 4);
+        assert!(encode(&img, OutputFormat::Jpeg, Some(50)).is_ok());
+        assert!(encode(&img, OutputFormat::Jpeg, Some(0)).is_err());
+        for format in [OutputFormat::Png, OutputFormat::Webp, OutputFormat::Qoi] {
+            let err = encode(&img, format, Some(50))
+                .err()
+                .expect("quality should be rejected");
+            assert!(err.message.contains("always lossless"), "{}", err.message);
+            assert!(encode(&img, format, None).is_ok());
+        }
+    }
+
+    #[test]
+    fn zero_bounds_are_rejected() {
+        assert!(fit_within(blank(10, 10), (0, 0), 1.0, Some(0), None).is_err());
+    }
+}
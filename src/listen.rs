@@ -0,0 +1,135 @@
+use std::fmt;
+#[cfg(unix)]
+use std::path::PathBuf;
+
+/// Where the HTTP transport accepts connections.
+pub enum ListenAddr {
+    Tcp { host: String, port: u16 },
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // Bracket IPv6 literals so the port separator stays unambiguous
+            ListenAddr::Tcp { host, port } if host.contains(':') => write!(f, "[{host}]:{port}"),
+            ListenAddr::Tcp { host, port } => write!(f, "{host}:{port}"),
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// Bind a Unix domain socket, replacing a stale socket file left by a previous run.
+#[cfg(unix)]
+pub fn bind_unix(path: &std::path::Path) -> std::io::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if let Ok(meta) = std::fs::symlink_metadata(path)
+        && meta.file_type().is_socket()
+    {
+        std::fs::remove_file(path)?;
+    }
+    tokio::net::UnixListener::bind(path)
+}
+
+#[cfg(feature = "tls")]
+pub use self::tls::{TlsFiles, TlsListener};
+
+#[cfg(feature = "tls")]
+mod tls {
+    use std::net::SocketAddr;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::mpsc;
+    use tokio_rustls::TlsAcceptor;
+    use tokio_rustls::rustls::{self, pki_types::pem::PemObject};
+    use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+    use tokio_rustls::server::TlsStream;
+
+    /// Handshakes that take longer than this are dropped so they cannot pin resources.
+    const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// PEM certificate chain and private key for the HTTPS listener.
+    pub struct TlsFiles {
+        pub cert: PathBuf,
+        pub key: PathBuf,
+    }
+
+    fn server_config(files: &TlsFiles) -> Result<rustls::ServerConfig, Box<dyn std::error::Error>> {
+        let certs = CertificateDer::pem_file_iter(&files.cert)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Failed to read TLS certificate {}: {e}", files.cert.display()))?;
+        let key = PrivateKeyDer::from_pem_file(&files.key)
+            .map_err(|e| format!("Failed to read TLS key {}: {e}", files.key.display()))?;
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)?;
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(config)
+    }
+
+    /// TCP listener that terminates TLS. Handshakes run in their own tasks so a slow
+    /// client cannot hold up other connections; finished streams are queued for `accept`.
+    pub struct TlsListener {
+        incoming: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+        local_addr: SocketAddr,
+    }
+
+    impl TlsListener {
+        pub fn new(
+            mut tcp: TcpListener,
+            files: &TlsFiles,
+        ) -> Result<Self, Box<dyn std::error::Error>> {
+            let acceptor = TlsAcceptor::from(Arc::new(server_config(files)?));
+            let local_addr = tcp.local_addr()?;
+            let (tx, incoming) = mpsc::channel(64);
+
+            tokio::spawn(async move {
+                while !tx.is_closed() {
+                    let (stream, addr) = axum::serve::Listener::accept(&mut tcp).await;
+                    let (acceptor, tx) = (acceptor.clone(), tx.clone());
+                    tokio::spawn(async move {
+                        match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await
+                        {
+                            Ok(Ok(tls)) => {
+                                let _ = tx.send((tls, addr)).await;
+                            }
+                            Ok(Err(e)) => tracing::debug!("TLS handshake with {addr} failed: {e}"),
+                            Err(_) => tracing::debug!("TLS handshake with {addr} timed out"),
+                        }
+                    });
+                }
+            });
+
+            Ok(Self {
+                incoming,
+                local_addr,
+            })
+        }
+    }
+
+    impl axum::serve::Listener for TlsListener {
+        type Io = TlsStream<TcpStream>;
+        type Addr = SocketAddr;
+
+        async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+            match self.incoming.recv().await {
+                Some(conn) => conn,
+                // The accept task only exits once this listener is gone
+                None => std::future::pending().await,
+            }
+        }
+
+        fn local_addr(&self) -> std::io::Result<Self::Addr> {
+            Ok(self.local_addr)
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use rmcp::ErrorData as McpError;
+use rmcp::model::{
+    AnnotateAble, RawResource, RawResourceTemplate, Resource, ResourceContents, ResourceTemplate,
+};
+
+use crate::backend::{MonitorInfo, WindowInfo};
+use crate::encode::EncodedImage;
+
+const MONITORS_URI: &str = "screenshot://monitors";
+const PRIMARY_MONITOR_URI: &str = "screenshot://monitor/primary";
+const WINDOWS_URI: &str = "screenshot://windows";
+const MONITOR_PREFIX: &str = "screenshot://monitor/";
+const WINDOW_PREFIX: &str = "screenshot://window/";
+
+/// What a `screenshot://` URI refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceTarget {
+    /// JSON list of monitors.
+    Monitors,
+    /// JSON list of windows.
+    Windows,
+    /// Live capture of one monitor (`None` for the primary monitor).
+    Monitor(Option<u32>),
+    /// Live capture of one window.
+    Window(u32),
+}
+
+impl ResourceTarget {
+    pub fn parse(uri: &str) -> Result<Self, McpError> {
+        let not_found = || McpError::resource_not_found(format!("Unknown resource: {uri}"), None);
+        match uri {
+            MONITORS_URI => Ok(ResourceTarget::Monitors),
+            WINDOWS_URI => Ok(ResourceTarget::Windows),
+            PRIMARY_MONITOR_URI => Ok(ResourceTarget::Monitor(None)),
+            _ => {
+                if let Some(id) = uri.strip_prefix(MONITOR_PREFIX) {
+                    id.parse()
+                        .map(|id| ResourceTarget::Monitor(Some(id)))
+                        .map_err(|_| not_found())
+                } else if let Some(id) = uri.strip_prefix(WINDOW_PREFIX) {
+                    id.parse().map(ResourceTarget::Window).map_err(|_| not_found())
+                } else {
+                    Err(not_found())
+                }
+            }
+        }
+    }
+}
+
+pub fn monitor_uri(id: u32) -> String {
+    format!("{MONITOR_PREFIX}{id}")
+}
+
+pub fn window_uri(id: u32) -> String {
+    format!("{WINDOW_PREFIX}{id}")
+}
+
+fn resource(uri: String, name: String, description: String, mime_type: &str) -> Resource {
+    RawResource {
+        description: Some(description),
+        mime_type: Some(mime_type.to_string()),
+        ..RawResource::new(uri, name)
+    }
+    .no_annotation()
+}
+
+/// Concrete resources: the target listings plus one live capture per monitor and window.
+/// `windows` is `None` when the backend has no window support.
+pub fn list(monitors: &[MonitorInfo], windows: Option<&[WindowInfo]>) -> Vec<Resource> {
+    let mut resources = vec![
+        resource(
+            MONITORS_URI.to_string(),
+            "monitors".to_string(),
+            "All monitors with position, resolution and primary flag".to_string(),
+            "application/json",
+        ),
+        resource(
+            PRIMARY_MONITOR_URI.to_string(),
+            "primary monitor".to_string(),
+            "Latest frame of the primary monitor".to_string(),
+            "image/png",
+        ),
+    ];
+    for m in monitors {
+        resources.push(resource(
+            monitor_uri(m.id),
+            format!("monitor {}", m.name),
+            format!("Live capture of monitor {} ({}x{})", m.id, m.width, m.height),
+            "image/png",
+        ));
+    }
+
+    if let Some(windows) = windows {
+        resources.push(resource(
+            WINDOWS_URI.to_string(),
+            "windows".to_string(),
+            "All visible windows with title, app name, position and size".to_string(),
+            "application/json",
+        ));
+        for w in windows {
+            resources.push(resource(
+                window_uri(w.id),
+                format!("window {}", w.title),
+                format!("Live capture of {} window {}", w.app_name, w.id),
+                "image/png",
+            ));
+        }
+    }
+
+    resources
+}
+
+pub fn templates(supports_windows: bool) -> Vec<ResourceTemplate> {
+    let template = |uri_template: &str, name: &str, description: &str| {
+        RawResourceTemplate {
+            uri_template: uri_template.to_string(),
+            name: name.to_string(),
+            title: None,
+            description: Some(description.to_string()),
+            mime_type: Some("image/png".to_string()),
+            icons: None,
+        }
+        .no_annotation()
+    };
+
+    let mut templates = vec![template(
+        "screenshot://monitor/{id}",
+        "monitor",
+        "Live capture of the monitor with this ID (see screenshot://monitors)",
+    )];
+    if supports_windows {
+        templates.push(template(
+            "screenshot://window/{id}",
+            "window",
+            "Live capture of the window with this ID (see screenshot://windows)",
+        ));
+    }
+    templates
+}
+
+pub fn json_contents(uri: &str, json: String) -> ResourceContents {
+    ResourceContents::TextResourceContents {
+        uri: uri.to_string(),
+        mime_type: Some("application/json".to_string()),
+        text: json,
+        meta: None,
+    }
+}
+
+pub fn image_contents(uri: &str, image: &EncodedImage) -> ResourceContents {
+    ResourceContents::BlobResourceContents {
+        uri: uri.to_string(),
+        mime_type: Some(image.format.mime_type().to_string()),
+        blob: image.to_base64(),
+        meta: None,
+    }
+}
@@ -0,0 +1,950 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use image::{DynamicImage, RgbaImage};
+use rmcp::{
+    ErrorData as McpError, RoleServer, ServerHandler,
+    handler::server::{
+        tool::ToolRouter,
+        wrapper::{Json, Parameters},
+    },
+    model::*,
+    schemars, serde_json,
+    service::{NotificationContext, Peer, RequestContext},
+    tool, tool_handler, tool_router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{Backend, MonitorInfo, Rect, WindowInfo, WindowSelector, capture_scale};
+use crate::diff;
+use crate::encode::{self, OutputFormat};
+use crate::resources::{self, ResourceTarget};
+
+// -- Request structs for tool parameters --
+
+/// Output options shared by every screenshot tool.
+#[derive(Debug, Default, Deserialize, schemars::JsonSchema)]
+struct OutputOptions {
+    #[schemars(description = "File path to save the screenshot (extension added if missing)")]
+    save_path: Option<String>,
+    #[schemars(description = "Image format: png, jpeg, webp or qoi (default: from save_path extension, else png); must match a save_path extension")]
+    format: Option<OutputFormat>,
+    #[schemars(description = "JPEG quality from 1 to 100 (default 80); only valid with jpeg, the other formats are lossless")]
+    quality: Option<u8>,
+    #[schemars(description = "Downscale so the image is at most this many pixels wide (aspect ratio kept)")]
+    max_width: Option<u32>,
+    #[schemars(description = "Downscale so the image is at most this many pixels tall (aspect ratio kept)")]
+    max_height: Option<u32>,
+}
+
+/// When to capture, shared by every screenshot tool. Without either the capture is immediate.
+#[derive(Debug, Default, Deserialize, schemars::JsonSchema)]
+struct TimingOptions {
+    #[schemars(description = "Wait this many milliseconds before capturing, e.g. for a menu animation or tooltip delay")]
+    delay_ms: Option<u64>,
+    #[schemars(description = "Capture at this wall-clock time, in milliseconds since the Unix epoch (immediately if already past)")]
+    at_unix_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+struct TakeScreenshotRequest {
+    #[schemars(description = "Monitor ID to capture (omit for primary monitor)")]
+    monitor_id: Option<u32>,
+    #[schemars(description = "Capture a new frame even if the server's frame cache holds a recent one")]
+    #[serde(default)]
+    fresh: bool,
+    #[serde(flatten)]
+    timing: TimingOptions,
+    #[serde(flatten)]
+    output: OutputOptions,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+struct TakeScreenshotAllRequest {
+    #[schemars(description = "Capture a new frame even if the server's frame cache holds a recent one")]
+    #[serde(default)]
+    fresh: bool,
+    #[serde(flatten)]
+    timing: TimingOptions,
+    #[serde(flatten)]
+    output: OutputOptions,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+struct TakeScreenshotRegionRequest {
+    #[schemars(description = "X coordinate of the top-left corner (monitor-relative unless global is set)")]
+    x: i32,
+    #[schemars(description = "Y coordinate of the top-left corner (monitor-relative unless global is set)")]
+    y: i32,
+    #[schemars(description = "Width of the region in pixels")]
+    width: u32,
+    #[schemars(description = "Height of the region in pixels")]
+    height: u32,
+    #[schemars(description = "Monitor ID to capture from (omit for primary monitor; ignored when global is set)")]
+    monitor_id: Option<u32>,
+    #[schemars(description = "Interpret x/y in global desktop coordinates; the region may span several monitors")]
+    #[serde(default)]
+    global: bool,
+    #[schemars(description = "Capture a new frame even if the server's frame cache holds a recent one")]
+    #[serde(default)]
+    fresh: bool,
+    #[serde(flatten)]
+    timing: TimingOptions,
+    #[serde(flatten)]
+    output: OutputOptions,
+}
+
+/// Window selection criteria, shared by the tools that can capture a window.
+#[derive(Debug, Default, Deserialize, schemars::JsonSchema)]
+struct WindowCriteria {
+    #[schemars(description = "Window ID to capture (use list_windows to find IDs)")]
+    window_id: Option<u32>,
+    #[schemars(description = "Exact window title")]
+    title: Option<String>,
+    #[schemars(description = "Regular expression matched against the window title")]
+    title_regex: Option<String>,
+    #[schemars(description = "Application name (case-insensitive)")]
+    app_name: Option<String>,
+    #[schemars(description = "Process ID owning the window")]
+    pid: Option<u32>,
+    #[schemars(description = "Capture the window that currently has keyboard focus")]
+    #[serde(default)]
+    focused: bool,
+}
+
+impl WindowCriteria {
+    fn selector(&self) -> WindowSelector {
+        WindowSelector {
+            id: self.window_id,
+            title: self.title.clone(),
+            title_regex: self.title_regex.clone(),
+            app_name: self.app_name.clone(),
+            pid: self.pid,
+            focused: self.focused,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+struct TakeScreenshotWindowRequest {
+    #[serde(flatten)]
+    window: WindowCriteria,
+    #[serde(flatten)]
+    timing: TimingOptions,
+    #[serde(flatten)]
+    output: OutputOptions,
+}
+
+/// A rectangle relative to the top-left of a monitor.
+#[derive(Debug, Clone, Copy, Deserialize, schemars::JsonSchema)]
+struct RegionArgs {
+    #[schemars(description = "X coordinate of the top-left corner, relative to the monitor")]
+    x: i32,
+    #[schemars(description = "Y coordinate of the top-left corner, relative to the monitor")]
+    y: i32,
+    #[schemars(description = "Width of the region in pixels")]
+    width: u32,
+    #[schemars(description = "Height of the region in pixels")]
+    height: u32,
+}
+
+/// What to capture and how often, shared by `wait_for_stable` and `wait_for_change`.
+#[derive(Debug, Default, Deserialize, schemars::JsonSchema)]
+struct PollOptions {
+    #[schemars(description = "Monitor ID to capture (omit for primary monitor; not combinable with window criteria)")]
+    monitor_id: Option<u32>,
+    #[schemars(description = "Only compare and return this part of the monitor")]
+    region: Option<RegionArgs>,
+    #[serde(flatten)]
+    window: WindowCriteria,
+    #[schemars(description = "Fraction of changed pixels above which two frames count as different (default 0.001)")]
+    threshold: Option<f64>,
+    #[schemars(description = "Milliseconds between captures (default 100)")]
+    interval_ms: Option<u64>,
+    #[schemars(description = "Give up after this many milliseconds (default 5000 for wait_for_stable, 10000 for wait_for_change)")]
+    timeout_ms: Option<u64>,
+}
+
+impl PollOptions {
+    fn target(&self) -> Result<PollTarget, McpError> {
+        let selector = self.window.selector();
+        match (self.monitor_id, self.region, selector.is_empty()) {
+            (Some(_), _, false) => Err(McpError::invalid_params(
+                "Set either monitor_id or window criteria, not both",
+                None,
+            )),
+            (_, Some(_), false) => Err(McpError::invalid_params(
+                "region is only supported for monitors, not windows",
+                None,
+            )),
+            (monitor_id, None, true) => Ok(PollTarget::Monitor(monitor_id)),
+            (monitor_id, Some(region), true) => Ok(PollTarget::Region(monitor_id, region)),
+            (None, None, false) => Ok(PollTarget::Window(selector)),
+        }
+    }
+
+    /// The change threshold, poll interval and timeout, with defaults applied.
+    fn settings(&self, default_timeout: Duration) -> Result<(f64, Duration, Duration), McpError> {
+        let threshold = self.threshold.unwrap_or(0.001);
+        let interval = Duration::from_millis(self.interval_ms.unwrap_or(100));
+        let timeout = self
+            .timeout_ms
+            .map(Duration::from_millis)
+            .unwrap_or(default_timeout);
+        if !(0.0..=1.0).contains(&threshold) {
+            return Err(McpError::invalid_params(
+                "threshold must be between 0 and 1",
+                None,
+            ));
+        }
+        if timeout > MAX_CAPTURE_WAIT {
+            return Err(McpError::invalid_params(
+                format!(
+                    "timeout_ms must be at most {}",
+                    MAX_CAPTURE_WAIT.as_millis()
+                ),
+                None,
+            ));
+        }
+        Ok((threshold, interval, timeout))
+    }
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+struct WaitForStableRequest {
+    #[serde(flatten)]
+    poll: PollOptions,
+    #[schemars(description = "Number of consecutive matching frames that count as settled (default 3, at least 2)")]
+    frames: Option<u32>,
+    #[serde(flatten)]
+    output: OutputOptions,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+struct WaitForChangeRequest {
+    #[serde(flatten)]
+    poll: PollOptions,
+    #[serde(flatten)]
+    output: OutputOptions,
+}
+
+/// What the polling tools capture on every round.
+#[derive(Debug, Clone)]
+enum PollTarget {
+    Monitor(Option<u32>),
+    Region(Option<u32>, RegionArgs),
+    Window(WindowSelector),
+}
+
+// -- Structured tool output --
+
+#[derive(Serialize)]
+struct StableReport {
+    /// Whether the frames settled before the timeout
+    settled: bool,
+    /// Milliseconds from the first capture until the returned frame
+    settle_ms: u64,
+    /// Number of frames captured
+    frames_captured: u32,
+}
+
+#[derive(Serialize)]
+struct ChangeReport {
+    /// Whether the content changed before the timeout
+    changed: bool,
+    /// Milliseconds from the baseline capture until the change was seen, or until the timeout
+    elapsed_ms: u64,
+    /// Fraction of pixels that changed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    changed_fraction: Option<f64>,
+    /// Bounding box of the changed pixels in global desktop coordinates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bounds: Option<Rect>,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+struct MonitorList {
+    monitors: Vec<MonitorInfo>,
+}
+
+#[derive(Serialize, schemars::JsonSchema)]
+struct WindowList {
+    windows: Vec<WindowInfo>,
+}
+
+// -- Helper functions --
+
+/// Downscale, encode and optionally save `img`. `origin` is the global screen position of
+/// its top-left pixel and `pixel_scale` its pixels per desktop unit, reported back so the
+/// caller can map image points to the screen.
+fn screenshot_result(
+    img: DynamicImage,
+    origin: (i32, i32),
+    pixel_scale: f64,
+    opts: &OutputOptions,
+) -> Result<CallToolResult, McpError> {
+    let (img, mapping) =
+        encode::fit_within(img, origin, pixel_scale, opts.max_width, opts.max_height)?;
+    let format = OutputFormat::resolve(opts.format, opts.save_path.as_deref())?;
+    let encoded = encode::encode(&img, format, opts.quality)?;
+    let saved = opts.save_path.as_deref().map(|path| encoded.save(path)).transpose()?;
+    let mut content = vec![
+        Content::image(encoded.to_base64(), format.mime_type()),
+        Content::json(mapping)?,
+    ];
+    if let Some(path) = saved {
+        content.push(Content::text(format!("Screenshot saved to {path}")));
+    }
+    Ok(CallToolResult::success(content))
+}
+
+/// Sleep for `duration`, failing early if the client cancels the request.
+async fn sleep_unless_cancelled(
+    context: &RequestContext<RoleServer>,
+    duration: Duration,
+) -> Result<(), McpError> {
+    tokio::select! {
+        _ = context.ct.cancelled() => {
+            Err(McpError::internal_error("Request cancelled by the client", None))
+        }
+        _ = tokio::time::sleep(duration) => Ok(()),
+    }
+}
+
+// -- Server configuration --
+
+#[derive(Clone)]
+pub struct ServerConfig {
+    /// Upper bound on a single backend call (capture or listing).
+    pub capture_timeout: Duration,
+    /// How often subscribed resources are re-captured.
+    pub watch_interval: Duration,
+    /// Fraction of changed pixels above which a subscribed resource counts as updated.
+    pub watch_threshold: f64,
+    /// How often monitors are re-probed for hotplugs; zero disables the check.
+    pub hotplug_interval: Duration,
+}
+
+/// Longest wait `delay_ms` or `at_unix_ms` may ask for.
+const MAX_CAPTURE_WAIT: Duration = Duration::from_secs(600);
+
+/// How often progress is reported while waiting to capture.
+const WAIT_PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+// -- MCP Server --
+
+#[derive(Clone)]
+pub struct ScreenshotServer {
+    backend: Arc<Backend>,
+    config: ServerConfig,
+    /// Resource watchers for this session, keyed by subscribed URI.
+    subscriptions: Arc<Mutex<HashMap<String, tokio::task::JoinHandle<()>>>>,
+    tool_router: ToolRouter<Self>,
+}
+
+impl ScreenshotServer {
+    /// Run a blocking backend call on tokio's blocking pool, bounded by the capture timeout.
+    /// A hung xcap or KMS call then only ties up a blocking thread, not an async worker.
+    async fn run_backend<T, F>(&self, what: &'static str, f: F) -> Result<T, McpError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Backend) -> Result<T, McpError> + Send + 'static,
+    {
+        let backend = self.backend.clone();
+        let task = tokio::task::spawn_blocking(move || f(&backend));
+        let timeout = self.config.capture_timeout;
+        match tokio::time::timeout(timeout, task).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => Err(McpError::internal_error(
+                format!("{what} task failed: {e}"),
+                None,
+            )),
+            Err(_) => {
+                tracing::warn!(
+                    "{what} timed out after {} ms on {} backend",
+                    timeout.as_millis(),
+                    self.backend.name()
+                );
+                Err(McpError::internal_error(
+                    format!(
+                        "{what} timed out after {} ms; the {} backend did not respond",
+                        timeout.as_millis(),
+                        self.backend.name()
+                    ),
+                    None,
+                ))
+            }
+        }
+    }
+
+    /// Sleep until the capture time requested by `timing`, reporting progress to
+    /// the client when it sent a progress token. Fails if the request is cancelled.
+    async fn wait_for_capture_time(
+        &self,
+        timing: &TimingOptions,
+        context: &RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        let wait = match (timing.delay_ms, timing.at_unix_ms) {
+            (None, None) => return Ok(()),
+            (Some(_), Some(_)) => {
+                return Err(McpError::invalid_params(
+                    "Set either delay_ms or at_unix_ms, not both",
+                    None,
+                ));
+            }
+            (Some(ms), None) => Duration::from_millis(ms),
+            (None, Some(ms)) => (UNIX_EPOCH + Duration::from_millis(ms))
+                .duration_since(SystemTime::now())
+                .unwrap_or_default(),
+        };
+        if wait > MAX_CAPTURE_WAIT {
+            return Err(McpError::invalid_params(
+                format!(
+                    "Capture would wait {} ms; the limit is {} ms",
+                    wait.as_millis(),
+                    MAX_CAPTURE_WAIT.as_millis()
+                ),
+                None,
+            ));
+        }
+
+        let progress_token = context.meta.get_progress_token();
+        let start = Instant::now();
+        let deadline = start + wait;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(());
+            }
+            sleep_unless_cancelled(context, (deadline - now).min(WAIT_PROGRESS_INTERVAL)).await?;
+
+            if let Some(token) = &progress_token {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let param = ProgressNotificationParam {
+                    progress_token: token.clone(),
+                    progress: start.elapsed().min(wait).as_millis() as f64,
+                    total: Some(wait.as_millis() as f64),
+                    message: Some(format!("Capturing in {} ms", remaining.as_millis())),
+                };
+                if let Err(e) = context.peer.notify_progress(param).await {
+                    tracing::debug!("Failed to send capture progress: {e}");
+                }
+            }
+        }
+    }
+
+    /// Capture a fresh frame of `target`, returning it with the global position of
+    /// its top-left pixel and its pixels per desktop unit.
+    async fn capture_poll_target(
+        &self,
+        target: &PollTarget,
+    ) -> Result<(RgbaImage, (i32, i32), f64), McpError> {
+        match target.clone() {
+            PollTarget::Monitor(monitor_id) => {
+                self.run_backend("Screen capture", move |b| {
+                    let monitor = b.monitor_info(monitor_id)?;
+                    let rgba = b.capture_monitor(Some(monitor.id), true)?;
+                    let scale = capture_scale(monitor.width, rgba.width());
+                    Ok((rgba, (monitor.x, monitor.y), scale))
+                })
+                .await
+            }
+            PollTarget::Region(monitor_id, r) => {
+                self.run_backend("Region capture", move |b| {
+                    let region = b.capture_region(monitor_id, r.x, r.y, r.width, r.height, true)?;
+                    Ok((region.image.into_rgba8(), region.origin, region.scale))
+                })
+                .await
+            }
+            PollTarget::Window(selector) => {
+                self.run_backend("Window capture", move |b| {
+                    let (window, rgba) = b.capture_window(&selector)?;
+                    let scale = capture_scale(window.width, rgba.width());
+                    Ok((rgba, (window.x, window.y), scale))
+                })
+                .await
+            }
+        }
+    }
+
+    /// Capture the image behind a monitor or window resource.
+    /// `fresh` bypasses the frame cache.
+    async fn capture_target(
+        &self,
+        target: ResourceTarget,
+        fresh: bool,
+    ) -> Result<RgbaImage, McpError> {
+        match target {
+            ResourceTarget::Monitor(monitor_id) => {
+                self.run_backend("Screen capture", move |b| {
+                    let monitor = b.monitor_info(monitor_id)?;
+                    b.capture_monitor(Some(monitor.id), fresh)
+                })
+                .await
+            }
+            ResourceTarget::Window(window_id) => {
+                let selector = WindowSelector::by_id(window_id);
+                let (_, image) = self
+                    .run_backend("Window capture", move |b| b.capture_window(&selector))
+                    .await?;
+                Ok(image)
+            }
+            ResourceTarget::Monitors | ResourceTarget::Windows => Err(McpError::invalid_params(
+                "Resource is a listing, not a capture",
+                None,
+            )),
+        }
+    }
+
+    /// Re-capture a subscribed resource every `watch_interval` and notify the client
+    /// once it differs from the last notified frame by more than `watch_threshold`.
+    /// Runs until unsubscribed (the task is aborted) or the client goes away.
+    async fn watch_resource(self, uri: String, target: ResourceTarget, peer: Peer<RoleServer>) {
+        let mut interval = tokio::time::interval(self.config.watch_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut baseline: Option<RgbaImage> = None;
+
+        loop {
+            interval.tick().await;
+            if peer.is_transport_closed() {
+                tracing::debug!("Stopping watch of {uri}: client disconnected");
+                break;
+            }
+            let frame = match self.capture_target(target, true).await {
+                Ok(frame) => frame,
+                Err(e) => {
+                    tracing::debug!("Watch capture of {uri} failed: {}", e.message);
+                    continue;
+                }
+            };
+
+            if let Some(previous) = &baseline {
+                let diff = diff::compare(previous, &frame);
+                if diff.fraction() <= self.config.watch_threshold {
+                    continue;
+                }
+                tracing::debug!(
+                    "{uri} changed ({:.2}% of pixels), notifying",
+                    diff.fraction() * 100.0
+                );
+                let param = ResourceUpdatedNotificationParam { uri: uri.clone() };
+                if let Err(e) = peer.notify_resource_updated(param).await {
+                    tracing::debug!("Stopping watch of {uri}: {e}");
+                    break;
+                }
+            }
+            baseline = Some(frame);
+        }
+    }
+
+    /// Re-probe monitors every `hotplug_interval` and tell the client the resource
+    /// list changed when a monitor is plugged in or out or changes mode.
+    /// Runs until the client goes away.
+    async fn watch_hotplug(self, peer: Peer<RoleServer>) {
+        let mut interval = tokio::time::interval(self.config.hotplug_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut last_generation = None;
+
+        loop {
+            interval.tick().await;
+            if peer.is_transport_closed() {
+                tracing::debug!("Stopping hotplug watch: client disconnected");
+                break;
+            }
+            let generation = match self
+                .run_backend("Monitor re-probe", |b| Ok(b.refresh_monitors()))
+                .await
+            {
+                Ok(generation) => generation,
+                Err(e) => {
+                    tracing::debug!("Monitor re-probe failed: {}", e.message);
+                    continue;
+                }
+            };
+
+            if last_generation.is_some_and(|last| last != generation) {
+                tracing::info!("Monitors changed, notifying client");
+                if let Err(e) = peer.notify_resource_list_changed().await {
+                    tracing::debug!("Stopping hotplug watch: {e}");
+                    break;
+                }
+            }
+            last_generation = Some(generation);
+        }
+    }
+}
+
+#[tool_router]
+impl ScreenshotServer {
+    pub fn new(backend: Arc<Backend>, config: ServerConfig) -> Self {
+        let caps = backend.capabilities();
+        let mut router = Self::tool_router();
+
+        if !caps.supports_windows {
+            router.remove_route("take_screenshot_window");
+            router.remove_route("list_windows");
+            tracing::info!("Window tools removed (not supported by {} backend)", backend.name());
+        }
+
+        Self {
+            backend,
+            config,
+            subscriptions: Default::default(),
+            tool_router: router,
+        }
+    }
+
+    #[tool(description = "Take a full-screen screenshot. Returns a base64-encoded image (PNG by default). Optionally specify a monitor, an output format, a maximum size and/or a file path to save. The response includes a JSON coordinate mapping (origin and scale) for converting image points to screen coordinates.")]
+    async fn take_screenshot(
+        &self,
+        Parameters(req): Parameters<TakeScreenshotRequest>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        self.wait_for_capture_time(&req.timing, &context).await?;
+        let (monitor_id, fresh) = (req.monitor_id, req.fresh);
+        let (monitor, rgba) = self
+            .run_backend("Screen capture", move |b| {
+                let monitor = b.monitor_info(monitor_id)?;
+                let rgba = b.capture_monitor(Some(monitor.id), fresh)?;
+                Ok((monitor, rgba))
+            })
+            .await?;
+        let scale = capture_scale(monitor.width, rgba.width());
+        let img = DynamicImage::ImageRgba8(rgba);
+        screenshot_result(img, (monitor.x, monitor.y), scale, &req.output)
+    }
+
+    #[tool(description = "Take a screenshot of the whole virtual desktop, compositing all monitors at their positions into one image. Gaps between monitors are transparent.")]
+    async fn take_screenshot_all(
+        &self,
+        Parameters(req): Parameters<TakeScreenshotAllRequest>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        self.wait_for_capture_time(&req.timing, &context).await?;
+        let fresh = req.fresh;
+        let (rgba, origin, scale) = self
+            .run_backend("Desktop capture", move |b| b.capture_all(fresh))
+            .await?;
+        let img = DynamicImage::ImageRgba8(rgba);
+        screenshot_result(img, origin, scale, &req.output)
+    }
+
+    #[tool(description = "Take a screenshot of a specific screen region. Coordinates are relative to one monitor, or to the whole desktop with global=true (the region may then span monitors). Parts of the region outside every monitor are reported, not captured. Returns a base64-encoded image (PNG by default).")]
+    async fn take_screenshot_region(
+        &self,
+        Parameters(req): Parameters<TakeScreenshotRegionRequest>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        self.wait_for_capture_time(&req.timing, &context).await?;
+        let (x, y, width, height) = (req.x, req.y, req.width, req.height);
+        let (global, monitor_id, fresh) = (req.global, req.monitor_id, req.fresh);
+        let region = self
+            .run_backend("Region capture", move |b| {
+                if global {
+                    b.capture_desktop_region(x, y, width, height, fresh)
+                } else {
+                    b.capture_region(monitor_id, x, y, width, height, fresh)
+                }
+            })
+            .await?;
+        let mut result =
+            screenshot_result(region.image, region.origin, region.scale, &req.output)?;
+        if !region.off_screen.is_empty() {
+            let rects = serde_json::to_string(&region.off_screen).map_err(|e| {
+                McpError::internal_error(format!("Failed to serialize: {e}"), None)
+            })?;
+            result.content.push(Content::text(format!(
+                "Part of the requested region is off-screen and was not captured \
+                 (global x/y/width/height): {rects}"
+            )));
+        }
+        Ok(result)
+    }
+
+    #[tool(description = "Take a screenshot of one window, selected by window_id, exact title, title_regex, app_name, pid and/or focused. All given criteria must match exactly one window; ambiguous or empty matches are reported as errors. Returns a base64-encoded image (PNG by default).")]
+    async fn take_screenshot_window(
+        &self,
+        Parameters(req): Parameters<TakeScreenshotWindowRequest>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        self.wait_for_capture_time(&req.timing, &context).await?;
+        let selector = req.window.selector();
+        let (window, rgba) = self
+            .run_backend("Window capture", move |b| b.capture_window(&selector))
+            .await?;
+        let scale = capture_scale(window.width, rgba.width());
+        let img = DynamicImage::ImageRgba8(rgba);
+        screenshot_result(img, (window.x, window.y), scale, &req.output)
+    }
+
+    #[tool(description = "Capture a monitor or window repeatedly until consecutive frames stop changing, then return the settled frame. Use this after an action whose UI is still animating or rendering. Reports whether the frames settled and how long it took; on timeout the latest frame is returned.")]
+    async fn wait_for_stable(
+        &self,
+        Parameters(req): Parameters<WaitForStableRequest>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let target = req.poll.target()?;
+        let (threshold, interval, timeout) = req.poll.settings(Duration::from_secs(5))?;
+        let frames = req.frames.unwrap_or(3);
+        if frames < 2 {
+            return Err(McpError::invalid_params("frames must be at least 2", None));
+        }
+
+        let start = Instant::now();
+        let (mut frame, mut origin, mut scale) = self.capture_poll_target(&target).await?;
+        let mut captured = 1;
+        let mut matching = 1;
+        while matching < frames && start.elapsed() < timeout {
+            sleep_unless_cancelled(&context, interval).await?;
+            let (next, next_origin, next_scale) = self.capture_poll_target(&target).await?;
+            captured += 1;
+            let diff = diff::compare(&frame, &next);
+            if diff.fraction() <= threshold && next_origin == origin {
+                matching += 1;
+            } else {
+                matching = 1;
+            }
+            (frame, origin, scale) = (next, next_origin, next_scale);
+        }
+
+        let report = StableReport {
+            settled: matching >= frames,
+            settle_ms: start.elapsed().as_millis() as u64,
+            frames_captured: captured,
+        };
+        tracing::debug!(
+            "wait_for_stable: settled={} after {} ms and {} frames",
+            report.settled,
+            report.settle_ms,
+            report.frames_captured
+        );
+        let mut result =
+            screenshot_result(DynamicImage::ImageRgba8(frame), origin, scale, &req.output)?;
+        result.content.push(Content::json(report)?);
+        Ok(result)
+    }
+
+    #[tool(description = "Wait until a monitor, a region of it or a window changes, e.g. after clicking a button. A window that moves counts as changed. Captures a baseline, then polls until more than threshold of the pixels differ from it and returns the new frame with the bounding box of the change in global desktop coordinates. If nothing changes before the timeout, returns a \"no change\" result without an image.")]
+    async fn wait_for_change(
+        &self,
+        Parameters(req): Parameters<WaitForChangeRequest>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let target = req.poll.target()?;
+        let (threshold, interval, timeout) = req.poll.settings(Duration::from_secs(10))?;
+
+        let start = Instant::now();
+        let (baseline, baseline_origin, _) = self.capture_poll_target(&target).await?;
+        while start.elapsed() < timeout {
+            sleep_unless_cancelled(&context, interval).await?;
+            let (frame, origin, scale) = self.capture_poll_target(&target).await?;
+            let mut diff = diff::compare(&baseline, &frame);
+            if origin != baseline_origin {
+                // A moved window changed everywhere on screen, even if its content did not
+                diff = diff::FrameDiff::whole(&frame);
+            } else if diff.fraction() <= threshold {
+                continue;
+            }
+
+            let report = ChangeReport {
+                changed: true,
+                elapsed_ms: start.elapsed().as_millis() as u64,
+                changed_fraction: Some(diff.fraction()),
+                // The diff is in image pixels; report it in desktop units
+                bounds: diff.bounds.map(|r| {
+                    let r = r.scaled(1.0 / scale);
+                    Rect {
+                        x: r.x.saturating_add(origin.0),
+                        y: r.y.saturating_add(origin.1),
+                        ..r
+                    }
+                }),
+            };
+            tracing::debug!(
+                "wait_for_change: {:.2}% of pixels changed after {} ms",
+                diff.fraction() * 100.0,
+                report.elapsed_ms
+            );
+            let mut result =
+                screenshot_result(DynamicImage::ImageRgba8(frame), origin, scale, &req.output)?;
+            result.content.push(Content::json(report)?);
+            return Ok(result);
+        }
+
+        let report = ChangeReport {
+            changed: false,
+            elapsed_ms: start.elapsed().as_millis() as u64,
+            changed_fraction: None,
+            bounds: None,
+        };
+        Ok(CallToolResult::success(vec![
+            Content::text(format!("No change within {} ms", timeout.as_millis())),
+            Content::json(report)?,
+        ]))
+    }
+
+    #[tool(description = "List all visible windows with their ID, title, app name, position, size, and minimized/maximized state.")]
+    async fn list_windows(&self) -> Result<Json<WindowList>, McpError> {
+        let windows = self
+            .run_backend("Window listing", |b| b.list_windows())
+            .await?;
+        Ok(Json(WindowList { windows }))
+    }
+
+    #[tool(description = "List all monitors with their ID, name, position, resolution, and whether they are the primary monitor. Where the backend knows them, also refresh rate, rotation, scale factor, physical size in mm and the EDID make, model and serial.")]
+    async fn list_monitors(&self) -> Result<Json<MonitorList>, McpError> {
+        let monitors = self
+            .run_backend("Monitor listing", |b| b.list_monitors())
+            .await?;
+        Ok(Json(MonitorList { monitors }))
+    }
+}
+
+#[tool_handler]
+impl ServerHandler for ScreenshotServer {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            protocol_version: ProtocolVersion::V_2025_06_18,
+            capabilities: ServerCapabilities::builder()
+                .enable_tools()
+                .enable_resources()
+                .enable_resources_subscribe()
+                .enable_resources_list_changed()
+                .build(),
+            server_info: Implementation::from_build_env(),
+            instructions: Some(
+                "MCP server for taking screenshots, listing windows and monitors. \
+                 Monitors and windows are also exposed as screenshot:// resources."
+                    .to_string(),
+            ),
+        }
+    }
+
+    async fn on_initialized(&self, context: NotificationContext<RoleServer>) {
+        if self.backend.capabilities().supports_hotplug && !self.config.hotplug_interval.is_zero() {
+            tokio::spawn(self.clone().watch_hotplug(context.peer));
+        }
+    }
+
+    async fn list_resources(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        let supports_windows = self.backend.capabilities().supports_windows;
+        let (monitors, windows) = self
+            .run_backend("Resource listing", move |b| {
+                let windows = if supports_windows {
+                    Some(b.list_windows()?)
+                } else {
+                    None
+                };
+                Ok((b.list_monitors()?, windows))
+            })
+            .await?;
+        Ok(ListResourcesResult::with_all_items(resources::list(
+            &monitors,
+            windows.as_deref(),
+        )))
+    }
+
+    async fn list_resource_templates(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourceTemplatesResult, McpError> {
+        let supports_windows = self.backend.capabilities().supports_windows;
+        Ok(ListResourceTemplatesResult::with_all_items(
+            resources::templates(supports_windows),
+        ))
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        let uri = request.uri;
+        let target = ResourceTarget::parse(&uri)?;
+        if matches!(target, ResourceTarget::Windows | ResourceTarget::Window(_))
+            && !self.backend.capabilities().supports_windows
+        {
+            return Err(McpError::resource_not_found(
+                format!("{uri}: windows are not supported by the {} backend", self.backend.name()),
+                None,
+            ));
+        }
+
+        let contents = match target {
+            ResourceTarget::Monitors | ResourceTarget::Windows => {
+                let json = self
+                    .run_backend("Resource listing", move |b| {
+                        let json = if target == ResourceTarget::Monitors {
+                            serde_json::to_string_pretty(&b.list_monitors()?)
+                        } else {
+                            serde_json::to_string_pretty(&b.list_windows()?)
+                        };
+                        json.map_err(|e| {
+                            McpError::internal_error(format!("Failed to serialize: {e}"), None)
+                        })
+                    })
+                    .await?;
+                resources::json_contents(&uri, json)
+            }
+            ResourceTarget::Monitor(_) | ResourceTarget::Window(_) => {
+                let rgba = self.capture_target(target, false).await?;
+                let encoded =
+                    encode::encode(&DynamicImage::ImageRgba8(rgba), OutputFormat::Png, None)?;
+                resources::image_contents(&uri, &encoded)
+            }
+        };
+
+        Ok(ReadResourceResult {
+            contents: vec![contents],
+        })
+    }
+
+    async fn subscribe(
+        &self,
+        request: SubscribeRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        let uri = request.uri;
+        let target = ResourceTarget::parse(&uri)?;
+        match target {
+            ResourceTarget::Monitor(_) => {}
+            ResourceTarget::Window(_) if self.backend.capabilities().supports_windows => {}
+            _ => {
+                return Err(McpError::invalid_params(
+                    format!("{uri} does not support subscriptions; subscribe to a monitor or window capture"),
+                    None,
+                ));
+            }
+        }
+
+        match self.subscriptions.lock().unwrap().entry(uri.clone()) {
+            Entry::Occupied(watcher) if !watcher.get().is_finished() => {}
+            // Vacant, or the watcher stopped after a failed notification: start a new one
+            entry => {
+                tracing::info!("Subscribed to {uri}");
+                let watcher = tokio::spawn(self.clone().watch_resource(uri, target, context.peer));
+                entry.insert_entry(watcher);
+            }
+        }
+        Ok(())
+    }
+
+    async fn unsubscribe(
+        &self,
+        request: UnsubscribeRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        if let Some(watcher) = self.subscriptions.lock().unwrap().remove(&request.uri) {
+            watcher.abort();
+            tracing::info!("Unsubscribed from {}", request.uri);
+        }
+        Ok(())
+    }
+}